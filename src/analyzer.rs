@@ -1,32 +1,49 @@
 use std::{
     collections::{BinaryHeap, HashMap},
-    fs::File,
-    io::{self, BufReader, Read},
+    fs::Metadata,
+    ops::AddAssign,
     path::{Path, PathBuf},
-    time::Instant,
+    time::{Instant, SystemTime},
 };
 
 use crate::model::{AnalyzeOptions, DirectorySummary};
 use crate::{
+    cancel::Interrupt,
+    duplicates,
     error::AnalysisError,
-    model::{DuplicateGroup, FileInfoDirectory, FolderInfo},
+    model::{
+        extension_key, AgeHistogram, FileCategory, FileInfoDirectory, FileType, FolderInfo,
+        HardLink, InventoryEntry, MountPoint, OwnerBreakdown, OwnerUsage, Snapshot, SnapshotEntry,
+        TypeBreakdown, TypeUsage, Usage,
+    },
+    owners,
+    progress::ScanProgress,
+    tree::SizeTree,
+    utils, walker,
 };
-use sha2::{Digest, Sha256};
-use walkdir::{DirEntry, WalkDir};
+use ignore::WalkState;
 
+/// Walks `option.path()` and summarizes sizes, counts and whichever extra
+/// reports the options ask for (duplicates, type and owner breakdowns, age
+/// histogram, snapshot, inventory, size tree).
+///
+/// When the options' cancel token or timeout stops it early, the summary
+/// covers what was scanned and hashed by then and
+/// [`incomplete`](DirectorySummary::incomplete) says why.
 pub fn directory_analyzer(option: &AnalyzeOptions) -> Result<DirectorySummary, AnalysisError> {
     let start_time = Instant::now();
+    let interrupt = Interrupt::new(option.cancel_token(), option.timeout());
+    let hash_pool = duplicates::HashPool::new(option.threads());
     let path = option
         .path()
         .canonicalize()
-        .map_err(|e| AnalysisError::IoError(e))?;
+        .map_err(AnalysisError::IoError)?;
 
     if !path.exists() {
         return Err(AnalysisError::PathNotFound(path));
     }
-    let mut size_groups: HashMap<u64, Vec<PathBuf>> = HashMap::new();
 
-    let mut summary = DirectorySummary::new(
+    let summary = DirectorySummary::new(
         0,
         0,
         0,
@@ -40,127 +57,596 @@ pub fn directory_analyzer(option: &AnalyzeOptions) -> Result<DirectorySummary, A
             None
         },
     );
+    let mut totals = Totals {
+        option,
+        root: &path,
+        summary,
+        size_groups: HashMap::new(),
+        top_files: BinaryHeap::with_capacity(option.top_n()),
+        sparse_files: BinaryHeap::new(),
+        folder_sizes: HashMap::new(),
+        snapshot: option.snapshot().then(|| {
+            Snapshot::new(
+                path.clone(),
+                option.snapshot_hashes().then_some(option.hash_algorithm()),
+            )
+        }),
+        inventory: option.inventory().then(Vec::new),
+        size_tree: option.size_tree().then(|| SizeTree::new(path.clone())),
+        extension_usage: HashMap::new(),
+        category_usage: HashMap::new(),
+        folder_type_sizes: HashMap::new(),
+        age_histogram: option
+            .age_buckets()
+            .as_ref()
+            .map(|buckets| AgeHistogram::new(option.age_field(), buckets)),
+        stale_files: BinaryHeap::new(),
+        now: SystemTime::now(),
+        user_usage: HashMap::new(),
+        group_usage: HashMap::new(),
+        user_folders: HashMap::new(),
+    };
+    // Files with several links, by (device, inode); counted once after the walk
+    let mut linked: HashMap<(u64, u64), Vec<(PathBuf, Metadata)>> = HashMap::new();
 
-    let mut top_files = BinaryHeap::with_capacity(option.top_n());
-    let mut folder_sizes: HashMap<PathBuf, u64> = HashMap::new();
+    let builder = walker::walk_builder(
+        &path,
+        option.max_depth(),
+        option.follow_symlinks(),
+        option.respect_gitignore(),
+        option.threads(),
+    );
+    let visit = |entry: ignore::DirEntry, emitter: &walker::Emitter<Visited>| {
+        if interrupt.check() {
+            return WalkState::Quit;
+        }
+        if option.should_ignore(entry.path()) {
+            return WalkState::Skip;
+        }
+        match entry.metadata() {
+            Ok(metadata) => {
+                if walker::is_mount_point(&entry, &metadata) {
+                    let included = !option.one_file_system();
+                    emitter.emit(Visited::MountPoint(MountPoint::new(
+                        entry.path().to_path_buf(),
+                        included,
+                    )));
+                    if !included {
+                        return WalkState::Skip;
+                    }
+                }
+                emitter.emit(Visited::Entry(entry.into_path(), metadata))
+            }
+            Err(e) => eprintln!(
+                "Warning: Cannot read metadata for {}: {}",
+                entry.path().display(),
+                e
+            ),
+        }
+        WalkState::Continue
+    };
 
-    for entry in WalkDir::new(&path)
-        .follow_links(option.follow_symlinks())
-        .max_depth(option.max_depth().unwrap_or(usize::MAX))
-        .into_iter()
-        .filter_entry(|e| !option.should_ignore(e.path()))
-        .filter_map(handle_dir_entry)
-    {
-        let metadata = match entry.metadata() {
-            Ok(m) => m,
-            Err(e) => {
-                eprintln!(
-                    "Warning: Cannot read metadata for {}: {}",
-                    entry.path().display(),
-                    e
-                );
-                continue;
+    let mut mount_points = Vec::new();
+    let mut progress = ScanProgress::new(option.progress());
+    walker::walk_parallel(&builder, visit, |visited| {
+        let (entry_path, metadata) = match visited {
+            Visited::Entry(entry_path, metadata) => (entry_path, metadata),
+            Visited::MountPoint(mount_point) => {
+                mount_points.push(mount_point);
+                return;
             }
         };
-
+        progress.add(
+            &entry_path,
+            if metadata.is_file() { metadata.len() } else { 0 },
+            false,
+        );
+        if let Some(inventory) = totals.inventory.as_mut() {
+            inventory.push(InventoryEntry::from_metadata(entry_path.clone(), &metadata));
+        }
+        if metadata.is_file() && metadata.len() < option.min_size().unwrap_or_default() {
+            return;
+        }
         if metadata.is_file() {
-            let size = metadata.len();
-
-            if size >= option.min_size().unwrap_or_default() {
-                summary.add_to_total_size(size);
-                summary.increment_file_count();
-
-                if option.detect_duplicates() {
-                    size_groups
-                        .entry(size)
-                        .or_default()
-                        .push(entry.path().to_path_buf());
-                }
+            match utils::hard_link_id(&metadata) {
+                Some(id) => linked.entry(id).or_default().push((entry_path, metadata)),
+                None => totals.add_file(entry_path, &metadata),
+            }
+        } else if metadata.is_dir() {
+            totals.summary.increment_folder_count();
+            if let Some(size_tree) = totals.size_tree.as_mut() {
+                size_tree.add_dir(&entry_path);
+            }
+        } else if metadata.is_symlink() {
+            totals.summary.increment_symlink_count();
+        }
+    });
 
-                top_files.push(FileInfoDirectory::new(entry.path().to_path_buf(), size));
-                if top_files.len() > option.top_n() {
-                    top_files.pop();
-                }
+    progress.finish();
 
-                let mut current = entry
-                    .path()
-                    .parent()
-                    .map(|p| p.to_path_buf())
-                    .unwrap_or_default();
-                while current.starts_with(&path) {
-                    *folder_sizes.entry(current.clone()).or_default() += size;
-                    current = match current.parent() {
-                        Some(p) => p.to_path_buf(),
-                        None => break,
-                    };
+    // The first path of each inode (in path order, so results don't depend on
+    // walk order) carries its size; the other links are counted as files of
+    // no size, so folders holding only links still show them
+    let mut links: Vec<Vec<(PathBuf, Metadata)>> = linked.into_values().collect();
+    for paths in links.iter_mut() {
+        paths.sort_by(|a, b| a.0.cmp(&b.0));
+    }
+    links.sort_by(|a, b| a[0].0.cmp(&b[0].0));
+    // Extra links by the path carrying their inode's size, for duplicate groups
+    let mut links_by_target: HashMap<PathBuf, Vec<HardLink>> = HashMap::new();
+    for paths in links {
+        let mut paths = paths.into_iter();
+        let Some((first_path, first_metadata)) = paths.next() else {
+            continue;
+        };
+        let extra: Vec<PathBuf> = paths
+            .map(|(link_path, metadata)| {
+                totals.summary.increment_file_count();
+                totals.add_snapshot_entry(&link_path, &metadata);
+                if let Some(size_tree) = totals.size_tree.as_mut() {
+                    size_tree.add_file(&link_path, 0, metadata.modified().ok());
                 }
+                link_path
+            })
+            .collect();
+        if !extra.is_empty() {
+            totals.summary.add_hard_link(first_metadata.len(), extra.len() as u64);
+            if option.detect_duplicates() {
+                let links = extra
+                    .into_iter()
+                    .map(|link_path| HardLink::new(link_path, first_path.clone()))
+                    .collect();
+                links_by_target.insert(first_path.clone(), links);
             }
-        } else if metadata.is_dir() {
-            summary.increment_folder_count();
-        } else if metadata.is_symlink() {
-            summary.increment_symlink_count();
         }
+        totals.add_file(first_path, &first_metadata);
     }
 
+    let Totals {
+        mut summary,
+        size_groups,
+        top_files,
+        sparse_files,
+        folder_sizes,
+        snapshot,
+        inventory,
+        size_tree,
+        extension_usage,
+        category_usage,
+        folder_type_sizes,
+        age_histogram,
+        stale_files,
+        user_usage,
+        group_usage,
+        user_folders,
+        ..
+    } = totals;
+
     let mut top_folders = BinaryHeap::new();
-    for (path, size) in folder_sizes {
-        top_folders.push(FolderInfo::new(path, size));
+    for (path, usage) in folder_sizes {
+        let folder = if option.folders_by_type().is_some() {
+            match folder_type_sizes.get(&path) {
+                Some(type_size) => FolderInfo::new(path, usage).with_type_size(*type_size),
+                // Folders without any file of the type aren't ranked at all
+                None => continue,
+            }
+        } else {
+            FolderInfo::new(path, usage).with_rank(usage.get(option.usage()))
+        };
+        top_folders.push(folder);
         if top_folders.len() > option.top_n() {
             top_folders.pop();
         }
     }
 
+    if option.by_type() {
+        let mut extensions = type_usages(extension_usage);
+        extensions.truncate(option.top_n());
+        let categories = type_usages(
+            category_usage
+                .into_iter()
+                .map(|(category, usage)| (category.as_str().to_string(), usage))
+                .collect(),
+        );
+        summary.set_usage_by_type(TypeBreakdown::new(extensions, categories));
+    }
+
     if option.detect_duplicates() {
-        //Calculate hash for files of the same size
-        for (size, files) in size_groups.into_iter().filter(|(_, files)| files.len() > 1) {
-            let mut hash_groups: HashMap<String, Vec<PathBuf>> = HashMap::new();
-            for path in files {
-                if let Ok(hash) = calculate_file_hash(&path) {
-                    hash_groups.entry(hash).or_default().push(path);
-                }
+        let mut groups = duplicates::find_duplicates(
+            size_groups,
+            option.hash_algorithm(),
+            &hash_pool,
+            option.progress(),
+            &interrupt,
+        );
+        for group in groups.iter_mut() {
+            let links = group
+                .files()
+                .iter()
+                .filter_map(|file| links_by_target.remove(file))
+                .flatten()
+                .collect();
+            group.set_hard_links(links);
+        }
+        *summary.duplicates_mut() = Some(groups);
+    }
+
+    if option.by_owner() {
+        let user_names = owners::user_names();
+        let group_names = owners::group_names();
+        let mut users = owner_usages(user_usage, &user_names);
+        let mut user_folders = user_folders;
+        for user in users.iter_mut() {
+            let folders = user_folders.remove(&user.id()).unwrap_or_default();
+            user.set_largest_folders(largest_folders(folders, option));
+        }
+        summary.set_usage_by_owner(OwnerBreakdown::new(
+            users,
+            owner_usages(group_usage, &group_names),
+        ));
+    }
+
+    if let Some(mut age_histogram) = age_histogram {
+        age_histogram.set_stale_files(stale_files.into_sorted_vec());
+        summary.set_age_histogram(age_histogram);
+    }
+
+    if let Some(mut snapshot) = snapshot {
+        snapshot.files_mut().sort_by(|a, b| a.path().cmp(b.path()));
+        if let Some(algorithm) = snapshot.hash_algorithm() {
+            let paths: Vec<PathBuf> = snapshot.files().iter().map(|f| path.join(f.path())).collect();
+            let files: Vec<(&Path, u64)> = paths
+                .iter()
+                .zip(snapshot.files())
+                .map(|(p, f)| (p.as_path(), *f.size()))
+                .collect();
+            let hashes = duplicates::hash_files(
+                &files,
+                algorithm,
+                &hash_pool,
+                option.progress(),
+                &interrupt,
+            );
+            for (entry, hash) in snapshot.files_mut().iter_mut().zip(hashes) {
+                entry.set_hash(hash);
             }
-            for (hash, paths) in hash_groups {
-                if paths.len() > 1 {
-                    summary
-                        .duplicates_mut()
-                        .as_mut()
-                        .unwrap()
-                        .push(DuplicateGroup::new(paths, size, hash));
-                }
+        }
+        summary.set_snapshot(snapshot);
+    }
+
+    if let Some(mut inventory) = inventory {
+        inventory.sort_by(|a, b| a.path().cmp(b.path()));
+        if option.inventory_hashes() {
+            let files: Vec<&mut InventoryEntry> = inventory
+                .iter_mut()
+                .filter(|entry| entry.file_type() == Some(FileType::File))
+                .collect();
+            let paths: Vec<(&Path, u64)> = files
+                .iter()
+                .map(|entry| (entry.path().as_path(), *entry.size()))
+                .collect();
+            let hashes = duplicates::hash_files(
+                &paths,
+                option.hash_algorithm(),
+                &hash_pool,
+                option.progress(),
+                &interrupt,
+            );
+            for (entry, hash) in files.into_iter().zip(hashes) {
+                entry.set_hash(hash);
             }
         }
+        summary.set_inventory(inventory);
     }
 
+    if let Some(size_tree) = size_tree {
+        summary.set_size_tree(size_tree);
+    }
     summary.set_largest_files(top_files.into_sorted_vec());
     summary.set_largest_folders(top_folders.into_sorted_vec());
+    summary.set_sparse_files(sparse_files.into_sorted_vec());
+    mount_points.sort_by(|a, b| a.path().cmp(b.path()));
+    summary.set_mount_points(mount_points);
+    summary.set_incomplete(interrupt.reason());
     summary.set_duration(start_time.elapsed());
 
     Ok(summary)
 }
 
-fn handle_dir_entry(entry: Result<DirEntry, walkdir::Error>) -> Option<DirEntry> {
-    match entry {
-        Ok(entry) => Some(entry),
-        Err(e) => {
-            eprintln!("Warning: {}", e);
-            None
+enum Visited {
+    Entry(PathBuf, Metadata),
+    MountPoint(MountPoint),
+}
+
+// Everything accumulated per counted file on the aggregating thread
+struct Totals<'a> {
+    option: &'a AnalyzeOptions,
+    root: &'a Path,
+    summary: DirectorySummary,
+    size_groups: HashMap<u64, Vec<PathBuf>>,
+    top_files: BinaryHeap<FileInfoDirectory>,
+    sparse_files: BinaryHeap<FileInfoDirectory>,
+    folder_sizes: HashMap<PathBuf, Usage>,
+    snapshot: Option<Snapshot>,
+    // Every visited entry, with --export-csv / --export-sqlite
+    inventory: Option<Vec<InventoryEntry>>,
+    size_tree: Option<SizeTree>,
+    // (bytes, files) per extension and per category, with --by-type
+    extension_usage: HashMap<String, (u64, u64)>,
+    category_usage: HashMap<FileCategory, (u64, u64)>,
+    folder_type_sizes: HashMap<PathBuf, u64>,
+    age_histogram: Option<AgeHistogram>,
+    stale_files: BinaryHeap<FileInfoDirectory>,
+    // Ages are measured from the start of the scan
+    now: SystemTime,
+    // (bytes, files) per uid and gid, and each user's folders, with --by-owner
+    user_usage: HashMap<u32, (u64, u64)>,
+    group_usage: HashMap<u32, (u64, u64)>,
+    user_folders: HashMap<u32, HashMap<PathBuf, Usage>>,
+}
+
+impl Totals<'_> {
+    fn add_file(&mut self, entry_path: PathBuf, metadata: &Metadata) {
+        let option = self.option;
+        let usage = Usage::from_metadata(metadata);
+        let size = usage.apparent();
+
+        self.summary.add_to_total_size(usage);
+        self.summary.increment_file_count();
+
+        if option.detect_duplicates() {
+            self.size_groups
+                .entry(size)
+                .or_default()
+                .push(entry_path.clone());
+        }
+
+        self.top_files.push(
+            FileInfoDirectory::new(entry_path.clone(), usage).with_rank(usage.get(option.usage())),
+        );
+        if self.top_files.len() > option.top_n() {
+            self.top_files.pop();
+        }
+
+        if is_sparse(usage) {
+            // Ranked by how much of the length isn't allocated
+            self.sparse_files.push(
+                FileInfoDirectory::new(entry_path.clone(), usage)
+                    .with_rank(usage.apparent() - usage.disk()),
+            );
+            if self.sparse_files.len() > option.top_n() {
+                self.sparse_files.pop();
+            }
+        }
+
+        add_to_folder_sizes(&mut self.folder_sizes, self.root, &entry_path, usage);
+        self.add_snapshot_entry(&entry_path, metadata);
+
+        let counted = usage.get(option.usage());
+        if option.by_type() {
+            let (bytes, files) = self
+                .extension_usage
+                .entry(extension_key(&entry_path))
+                .or_default();
+            *bytes += counted;
+            *files += 1;
+            let (bytes, files) = self
+                .category_usage
+                .entry(FileCategory::of(self.root, &entry_path))
+                .or_default();
+            *bytes += counted;
+            *files += 1;
+        }
+        if let Some(histogram) = self.age_histogram.as_mut() {
+            let age = option
+                .age_field()
+                .of(metadata)
+                .and_then(|time| self.now.duration_since(time).ok())
+                .unwrap_or_default();
+            if histogram.add(age, counted) {
+                self.stale_files
+                    .push(FileInfoDirectory::new(entry_path.clone(), usage).with_rank(counted));
+                if self.stale_files.len() > option.top_n() {
+                    self.stale_files.pop();
+                }
+            }
+        }
+        if let Some((uid, gid)) = utils::owner_ids(metadata).filter(|_| option.by_owner()) {
+            let (bytes, files) = self.user_usage.entry(uid).or_default();
+            *bytes += counted;
+            *files += 1;
+            let (bytes, files) = self.group_usage.entry(gid).or_default();
+            *bytes += counted;
+            *files += 1;
+            add_to_folder_sizes(
+                self.user_folders.entry(uid).or_default(),
+                self.root,
+                &entry_path,
+                usage,
+            );
+        }
+        if let Some(selector) = option.folders_by_type() {
+            if selector.matches(self.root, &entry_path) {
+                add_to_folder_sizes(&mut self.folder_type_sizes, self.root, &entry_path, counted);
+            }
+        }
+
+        if let Some(size_tree) = self.size_tree.as_mut() {
+            size_tree.add_file(
+                &entry_path,
+                usage.get(option.usage()),
+                metadata.modified().ok(),
+            );
+        }
+    }
+
+    fn add_snapshot_entry(&mut self, entry_path: &Path, metadata: &Metadata) {
+        if let Some(snapshot) = self.snapshot.as_mut() {
+            let relative = entry_path.strip_prefix(self.root).unwrap_or(entry_path);
+            snapshot.push(SnapshotEntry::new(
+                relative.to_path_buf(),
+                metadata.len(),
+                metadata.modified().unwrap_or(std::time::UNIX_EPOCH),
+            ));
+        }
+    }
+}
+
+// Largest first, ties by id
+fn owner_usages(usage: HashMap<u32, (u64, u64)>, names: &HashMap<u32, String>) -> Vec<OwnerUsage> {
+    let mut usages: Vec<OwnerUsage> = usage
+        .into_iter()
+        .map(|(id, (size, count))| OwnerUsage::new(id, names.get(&id).cloned(), size, count))
+        .collect();
+    usages.sort_by(|a, b| b.size().cmp(a.size()).then_with(|| a.id().cmp(&b.id())));
+    usages
+}
+
+fn largest_folders(folders: HashMap<PathBuf, Usage>, option: &AnalyzeOptions) -> Vec<FolderInfo> {
+    let mut top_folders = BinaryHeap::new();
+    for (path, usage) in folders {
+        top_folders.push(FolderInfo::new(path, usage).with_rank(usage.get(option.usage())));
+        if top_folders.len() > option.top_n() {
+            top_folders.pop();
         }
     }
+    top_folders.into_sorted_vec()
 }
 
-fn calculate_file_hash(path: &Path) -> io::Result<String> {
-    let file = File::open(path)?;
-    let mut reader = BufReader::new(file);
-    let mut hasher = Sha256::new();
-    let mut buffer = [0; 8192]; // 8KB buffer
+// Largest first, ties by name
+fn type_usages(usage: HashMap<String, (u64, u64)>) -> Vec<TypeUsage> {
+    let mut usages: Vec<TypeUsage> = usage
+        .into_iter()
+        .map(|(name, (size, count))| TypeUsage::new(name, size, count))
+        .collect();
+    usages.sort_by(|a, b| b.size().cmp(a.size()).then_with(|| a.name().cmp(b.name())));
+    usages
+}
+
+/// Files at least this long are checked for sparseness
+const SPARSE_MIN_SIZE: u64 = 1024 * 1024;
+
+// Sparse: less than half of the file's length is actually allocated
+fn is_sparse(usage: Usage) -> bool {
+    usage.apparent() >= SPARSE_MIN_SIZE && usage.disk() < usage.apparent() / 2
+}
+
+/// Adds `size` to every folder between `file`'s parent and `root`, inclusive.
+pub fn add_to_folder_sizes<T: AddAssign + Copy + Default>(
+    folder_sizes: &mut HashMap<PathBuf, T>,
+    root: &Path,
+    file: &Path,
+    size: T,
+) {
+    let mut current = file.parent().map(|p| p.to_path_buf()).unwrap_or_default();
+    while current.starts_with(root) {
+        *folder_sizes.entry(current.clone()).or_default() += size;
+        current = match current.parent() {
+            Some(p) => p.to_path_buf(),
+            None => break,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::model::{AnalyzeOptionsBuilder, CancelToken, StopReason};
 
-    loop {
-        let count = reader.read(&mut buffer)?;
-        if count == 0 {
-            break;
+    #[test]
+    fn hard_links_count_once_and_are_told_apart_from_copies() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path().canonicalize().unwrap();
+        for dir in ["a", "b", "c"] {
+            fs::create_dir(root.join(dir)).unwrap();
         }
-        hasher.update(&buffer[..count]);
+        fs::write(root.join("a/f"), vec![b'x'; 1000]).unwrap();
+        fs::hard_link(root.join("a/f"), root.join("b/g")).unwrap();
+        fs::write(root.join("c/copy"), vec![b'x'; 1000]).unwrap();
+        fs::write(root.join("c/other"), vec![b'y'; 10]).unwrap();
+
+        let options = AnalyzeOptions::builder(root.clone())
+            .with_duplicates(true)
+            .with_size_tree()
+            .build()
+            .unwrap();
+        let mut summary = directory_analyzer(&options).unwrap();
+
+        // The linked inode counts once, its second link as a file
+        assert_eq!(*summary.total_size(), 2010);
+        assert_eq!(*summary.file_count(), 4);
+        let hard_links = summary.hard_links();
+        assert_eq!(
+            (*hard_links.inodes(), *hard_links.extra_links(), *hard_links.bytes_saved()),
+            (1, 1, 1000)
+        );
+
+        // One group of the real copies, with the link listed against its target
+        let groups = summary.duplicates().as_ref().unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].files(), &[root.join("a/f"), root.join("c/copy")]);
+        let links: Vec<(&PathBuf, &PathBuf)> = groups[0]
+            .hard_links()
+            .iter()
+            .map(|link| (link.path(), link.target()))
+            .collect();
+        assert_eq!(links, [(&root.join("b/g"), &root.join("a/f"))]);
+
+        // The tree agrees with the summary, and shows the link's folder
+        let tree = summary.take_size_tree().unwrap();
+        let top = tree.node(tree.root());
+        assert_eq!((*top.size(), *top.file_count()), (2010, 4));
+        let linked = tree.node(tree.find(&root.join("b")).unwrap());
+        assert_eq!((*linked.size(), *linked.file_count()), (0, 1));
+        assert!(tree.find(&root.join("b/g")).is_some());
     }
 
-    Ok(format!("{:x}", hasher.finalize()))
+    #[test]
+    fn stopped_scans_return_partial_results() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path().canonicalize().unwrap();
+        for name in ["a", "b", "c"] {
+            fs::write(root.join(name), b"data").unwrap();
+        }
+        let partial = |builder: AnalyzeOptionsBuilder| {
+            let options = builder.with_duplicates(true).with_inventory(false).build().unwrap();
+            directory_analyzer(&options).unwrap()
+        };
+
+        let cancel = CancelToken::new();
+        cancel.cancel();
+        let summary = partial(AnalyzeOptions::builder(root.clone()).with_cancel_token(cancel));
+        assert_eq!(summary.incomplete(), Some(StopReason::Cancelled));
+        assert!(*summary.file_count() < 3);
+        assert!(summary.inventory().is_some());
+        assert_eq!(StopReason::Cancelled.exit_code(), 130);
+
+        let summary = partial(AnalyzeOptions::builder(root).with_timeout(Some(Duration::ZERO)));
+        assert_eq!(summary.incomplete(), Some(StopReason::TimedOut));
+        assert!(summary.duplicates().is_some());
+        assert_eq!(StopReason::TimedOut.exit_code(), 124);
+    }
+
+    #[test]
+    fn inventory_lists_files_below_min_size() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path().canonicalize().unwrap();
+        fs::write(root.join("big"), vec![b'x'; 100]).unwrap();
+        fs::write(root.join("small"), b"x").unwrap();
+
+        let options = AnalyzeOptions::builder(root.clone())
+            .with_min_size(Some("10".to_string()))
+            .with_inventory(false)
+            .build()
+            .unwrap();
+        let summary = directory_analyzer(&options).unwrap();
+
+        assert_eq!(*summary.file_count(), 1);
+        let paths: Vec<&PathBuf> =
+            summary.inventory().as_ref().unwrap().iter().map(|entry| entry.path()).collect();
+        assert_eq!(paths, [&root, &root.join("big"), &root.join("small")]);
+    }
 }
@@ -0,0 +1,47 @@
+use std::{
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+use crate::model::{CancelToken, StopReason};
+
+/// Decides when a running analysis should stop early, from the cancel token
+/// and the deadline; shared by the walker and hash threads.
+pub struct Interrupt<'a> {
+    token: Option<&'a CancelToken>,
+    deadline: Option<Instant>,
+    // Set by the first check that sees a reason to stop, so every thread
+    // agrees on it
+    stopped: OnceLock<StopReason>,
+}
+
+impl<'a> Interrupt<'a> {
+    pub fn new(token: Option<&'a CancelToken>, timeout: Option<Duration>) -> Self {
+        Interrupt {
+            token,
+            deadline: timeout.map(|timeout| Instant::now() + timeout),
+            stopped: OnceLock::new(),
+        }
+    }
+
+    /// Whether work should stop now.
+    pub fn check(&self) -> bool {
+        if self.stopped.get().is_some() {
+            return true;
+        }
+        let reason = if self.token.is_some_and(|token| token.is_cancelled()) {
+            StopReason::Cancelled
+        } else if self.deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            StopReason::TimedOut
+        } else {
+            return false;
+        };
+        let _ = self.stopped.set(reason);
+        true
+    }
+
+    /// Why work stopped, if any check asked it to.
+    pub fn reason(&self) -> Option<StopReason> {
+        self.stopped.get().copied()
+    }
+}
@@ -1,23 +1,280 @@
+use std::collections::HashSet;
 use std::error::Error;
+use std::io::{self, IsTerminal, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
 
-use crate::analyzer::directory_analyzer;
-use crate::model;
-use crate::search::file_finder;
-use crate::utils;
+use serde::Serialize;
 
-pub fn display_directory_analyzer(option: model::AnalyzeOptions) -> Result<(), Box<dyn Error>> {
-    let summary = directory_analyzer(&option)?;
+use fs_tool::model::{self, FileType, SizeMetric};
+use fs_tool::utils;
+use fs_tool::{
+    directory_analyzer, export_csv, export_sqlite, file_finder, save_html_report, save_snapshot,
+    snapshot_diff, stream_files, AnalysisError, NodeKind, OptionsError, SizeTree, SortKey,
+};
+
+use crate::status::StatusLine;
+use crate::tui;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+    Jsonl,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            "jsonl" | "ndjson" => Ok(OutputFormat::Jsonl),
+            _ => Err(format!(
+                "Invalid output format: '{}'. Valid values are: text, json, jsonl",
+                s
+            )),
+        }
+    }
+}
+
+pub struct AnalyzeOutput {
+    format: OutputFormat,
+    save_snapshot: Option<PathBuf>,
+    html: Option<PathBuf>,
+    export_csv: Option<PathBuf>,
+    export_sqlite: Option<PathBuf>,
+    interactive: bool,
+    // Levels of the size tree to print; `None` leaves the tree out
+    tree_depth: Option<usize>,
+    // Progress line to clear once the scan is done
+    status: Option<Arc<StatusLine>>,
+}
+
+impl AnalyzeOutput {
+    pub fn new(format: OutputFormat) -> Self {
+        AnalyzeOutput {
+            format,
+            save_snapshot: None,
+            html: None,
+            export_csv: None,
+            export_sqlite: None,
+            interactive: false,
+            tree_depth: None,
+            status: None,
+        }
+    }
+
+    pub fn with_interactive(mut self, interactive: bool) -> Self {
+        self.interactive = interactive;
+        self
+    }
+
+    pub fn interactive(&self) -> bool {
+        self.interactive
+    }
+
+    /// Prints the directory tree below the report, `depth` levels deep.
+    pub fn with_tree(mut self, depth: usize) -> Self {
+        self.tree_depth = Some(depth);
+        self
+    }
+
+    pub fn tree_depth(&self) -> Option<usize> {
+        self.tree_depth
+    }
+
+    pub fn with_save_snapshot(mut self, path: Option<PathBuf>) -> Self {
+        self.save_snapshot = path;
+        self
+    }
+
+    pub fn format(&self) -> OutputFormat {
+        self.format
+    }
+
+    pub fn save_snapshot(&self) -> &Option<PathBuf> {
+        &self.save_snapshot
+    }
+
+    pub fn with_html(mut self, path: Option<PathBuf>) -> Self {
+        self.html = path;
+        self
+    }
+
+    pub fn html(&self) -> &Option<PathBuf> {
+        &self.html
+    }
+
+    pub fn with_export_csv(mut self, path: Option<PathBuf>) -> Self {
+        self.export_csv = path;
+        self
+    }
+
+    pub fn export_csv(&self) -> &Option<PathBuf> {
+        &self.export_csv
+    }
+
+    pub fn with_export_sqlite(mut self, path: Option<PathBuf>) -> Self {
+        self.export_sqlite = path;
+        self
+    }
+
+    pub fn export_sqlite(&self) -> &Option<PathBuf> {
+        &self.export_sqlite
+    }
+
+    pub fn with_status(mut self, status: Option<Arc<StatusLine>>) -> Self {
+        self.status = status;
+        self
+    }
+
+    pub fn status(&self) -> Option<&StatusLine> {
+        self.status.as_deref()
+    }
+}
+
+#[derive(Serialize)]
+struct AnalysisReport<'a> {
+    #[serde(serialize_with = "utils::serialize_path")]
+    path: PathBuf,
+    #[serde(flatten)]
+    summary: &'a model::DirectorySummary,
+}
+
+#[derive(Serialize)]
+struct SearchReport<'a> {
+    #[serde(serialize_with = "utils::serialize_path")]
+    path: PathBuf,
+    #[serde(flatten)]
+    result: &'a model::SearchResult,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum SearchEvent<'a> {
+    Match(&'a model::FileInfoSearch),
+    Summary {
+        #[serde(serialize_with = "utils::serialize_path")]
+        path: PathBuf,
+        files_found: u64,
+        files_searched: u64,
+        binary_skipped: u64,
+        total_size: u64,
+        search_time_ms: u64,
+    },
+}
+
+/// Runs the analysis and prints or exports it as `output` asks. Returns why
+/// the scan stopped early, if it did; the partial results are still shown.
+pub fn display_directory_analyzer(
+    option: model::AnalyzeOptions,
+    output: &AnalyzeOutput,
+) -> Result<Option<model::StopReason>, Box<dyn Error>> {
+    if output.interactive() && output.format() != OutputFormat::Text {
+        return Err(OptionsError::Conflict("interactive mode only works with text output").into());
+    }
+    if output.interactive() && !io::stdout().is_terminal() {
+        return Err("Interactive mode requires a terminal".into());
+    }
+
+    let summary = directory_analyzer(&option);
+    clear_status(output.status());
+    let mut summary = summary?;
+    let incomplete = summary.incomplete();
+    if let Some(reason) = incomplete {
+        eprintln!("Warning: Scan {}, results are partial", reason.describe());
+    }
+    if let (Some(path), Some(snapshot)) = (output.save_snapshot(), summary.snapshot()) {
+        // Diffing against a partial snapshot would report the rest as removed
+        if incomplete.is_some() {
+            eprintln!("Warning: Snapshot not saved to {}: the scan is incomplete", path.display());
+        } else {
+            save_snapshot(snapshot, path)?;
+            eprintln!("Snapshot saved to {}", path.display());
+        }
+    }
+    if let Some(inventory) = summary.inventory() {
+        if let Some(path) = output.export_csv() {
+            export_csv(inventory, incomplete, path)?;
+            match incomplete {
+                Some(_) => eprintln!(
+                    "Partial inventory exported to {} (marked on its last line)",
+                    path.display()
+                ),
+                None => eprintln!("Inventory exported to {}", path.display()),
+            }
+        }
+        if let Some(path) = output.export_sqlite() {
+            export_sqlite(inventory, incomplete, path)?;
+            match incomplete {
+                Some(_) => eprintln!(
+                    "Partial inventory exported to {} (marked in its scan_info table)",
+                    path.display()
+                ),
+                None => eprintln!("Inventory exported to {}", path.display()),
+            }
+        }
+    }
+    if let Some(path) = output.html() {
+        save_html_report(&option, &summary, path)?;
+        eprintln!("HTML report saved to {}", path.display());
+    }
+
+    if output.interactive() {
+        let duplicates: HashSet<PathBuf> = summary
+            .duplicates()
+            .iter()
+            .flatten()
+            .flat_map(|group| group.files().iter().cloned())
+            .collect();
+        if let Some(size_tree) = summary.take_size_tree() {
+            tui::browse(size_tree, duplicates)?;
+            return Ok(incomplete);
+        }
+    }
+
+    let report = AnalysisReport {
+        path: option.path(),
+        summary: &summary,
+    };
+    match output.format() {
+        OutputFormat::Text => display_analysis_text(&option, output, &summary)?,
+        OutputFormat::Json => print_json(&report, true)?,
+        OutputFormat::Jsonl => print_json(&report, false)?,
+    }
+    Ok(incomplete)
+}
+
+fn display_analysis_text(
+    option: &model::AnalyzeOptions,
+    output: &AnalyzeOutput,
+    summary: &model::DirectorySummary,
+) -> Result<(), Box<dyn Error>> {
     println!("📊 File System Analysis Report");
     println!("📂 Path: {}", option.path().display());
-    println!(
-        "⏱️  Scan completed in {:.1} seconds\n",
-        summary.duration().as_secs_f64()
-    );
+    match summary.incomplete() {
+        Some(reason) => println!(
+            "⚠️  Scan {} after {:.1} seconds, results are partial\n",
+            reason.describe(),
+            summary.duration().as_secs_f64()
+        ),
+        None => println!(
+            "⏱️  Scan completed in {:.1} seconds\n",
+            summary.duration().as_secs_f64()
+        ),
+    }
     println!("Directory Summary:");
     println!(
         "├── Total size: {}",
         utils::format_size(summary.total_size())
     );
+    println!(
+        "├── Disk usage: {}",
+        utils::format_size(summary.disk_usage())
+    );
     println!("├── Files: {}", utils::format_number(summary.file_count()));
     println!(
         "├── Folders: {}",
@@ -28,16 +285,34 @@ pub fn display_directory_analyzer(option: model::AnalyzeOptions) -> Result<(), B
         utils::format_number(summary.symlink_count())
     );
 
-    println!("\nLargest Directories:");
+    if let (Some(depth), Some(tree)) = (output.tree_depth(), summary.size_tree()) {
+        display_size_tree(tree, depth);
+    }
+
+    let usage = option.usage();
+    match option.folders_by_type() {
+        Some(selector) => println!("\nLargest Directories by {}:", selector.name()),
+        None => println!("\nLargest Directories:"),
+    }
     for (idx, dir) in summary.largest_folders().iter().enumerate() {
         let path_str = dir.path().display().to_string();
         let truncated_path = utils::truncate_path(&path_str, 60);
-        println!(
-            "{}. {} {}",
-            idx + 1,
-            truncated_path,
-            utils::format_size(dir.size())
-        );
+        match (dir.type_size(), option.folders_by_type()) {
+            (Some(type_size), Some(selector)) => println!(
+                "{}. {} {} {} (of {})",
+                idx + 1,
+                truncated_path,
+                utils::format_size(&type_size),
+                selector.name(),
+                utils::format_size(dir.size())
+            ),
+            _ => println!(
+                "{}. {} {}",
+                idx + 1,
+                truncated_path,
+                format_usage(usage, dir.size(), dir.disk_usage())
+            ),
+        }
     }
 
     println!("\nLargest Files:");
@@ -48,7 +323,53 @@ pub fn display_directory_analyzer(option: model::AnalyzeOptions) -> Result<(), B
             "{}. {} {}",
             idx + 1,
             truncated_path,
-            utils::format_size(file.size())
+            format_usage(usage, file.size(), file.disk_usage())
+        );
+    }
+
+    if !summary.sparse_files().is_empty() {
+        println!("\nSparse Files:");
+        for (idx, file) in summary.sparse_files().iter().enumerate() {
+            let path_str = file.path().display().to_string();
+            let truncated_path = utils::truncate_path(&path_str, 60);
+            println!(
+                "{}. {} {} apparent, {} on disk",
+                idx + 1,
+                truncated_path,
+                utils::format_size(file.size()),
+                utils::format_size(file.disk_usage())
+            );
+        }
+    }
+
+    if let Some(breakdown) = summary.usage_by_type() {
+        display_type_breakdown(breakdown);
+    }
+
+    if let Some(histogram) = summary.age_histogram() {
+        display_age_histogram(histogram);
+    }
+
+    if let Some(breakdown) = summary.usage_by_owner() {
+        display_owner_breakdown(breakdown, usage);
+    }
+
+    display_mount_points(summary.mount_points());
+
+    let hard_links = summary.hard_links();
+    if *hard_links.inodes() > 0 {
+        println!("\nHard Links:");
+        println!(
+            "├── Multiply-linked files: {}",
+            utils::format_number(hard_links.inodes())
+        );
+        println!(
+            "├── Extra links: {}",
+            utils::format_number(hard_links.extra_links())
+        );
+        println!(
+            "└── Space saved: {}",
+            utils::format_size(hard_links.bytes_saved())
         );
     }
 
@@ -68,6 +389,12 @@ pub fn display_directory_analyzer(option: model::AnalyzeOptions) -> Result<(), B
                         file_index,
                         utils::truncate_path(&file.as_path().display().to_string(), 60)
                     );
+                    for link in group.hard_links().iter().filter(|l| l.target() == file) {
+                        println!(
+                            "\t\t   ↳ hard link: {}",
+                            utils::truncate_path(&link.path().display().to_string(), 60)
+                        );
+                    }
                 }
             }
         }
@@ -75,23 +402,334 @@ pub fn display_directory_analyzer(option: model::AnalyzeOptions) -> Result<(), B
     Ok(())
 }
 
-pub fn display_search_result(options: model::SearchOptions) -> Result<(), Box<dyn Error>> {
-    let result = file_finder(&options)?;
-    if options.content_pattern().is_some() {
-        display_content_search(&options, &result)
+fn display_type_breakdown(breakdown: &model::TypeBreakdown) {
+    // Categories cover every file, in whichever size --usage picked
+    let total = breakdown
+        .categories()
+        .iter()
+        .map(|category| *category.size())
+        .sum::<u64>()
+        .max(1) as f64;
+    let print_usages = |usages: &[model::TypeUsage]| {
+        for type_usage in usages {
+            println!(
+                "  {:<12} {:>10} {:>5.1}%  {} files",
+                type_usage.name(),
+                utils::format_size(type_usage.size()),
+                *type_usage.size() as f64 / total * 100.0,
+                utils::format_number(type_usage.count())
+            );
+        }
+    };
+    println!("\nUsage by Category:");
+    print_usages(breakdown.categories());
+    println!("\nUsage by Extension:");
+    print_usages(breakdown.extensions());
+}
+
+// Entries smaller than this share of the total are folded into "(others)"
+const TREE_MIN_SHARE: f64 = 0.01;
+const TREE_NAME_WIDTH: usize = 50;
+
+fn display_size_tree(tree: &SizeTree, depth: usize) {
+    let root = tree.node(tree.root());
+    let total = (*root.size()).max(1) as f64;
+    println!("\nDirectory Tree:");
+    print_tree_line(
+        &root.path().display().to_string(),
+        *root.size(),
+        total,
+        &format!("{} files", utils::format_number(root.file_count())),
+    );
+    print_tree_children(tree, tree.root(), "", depth, total);
+}
+
+fn print_tree_children(tree: &SizeTree, id: usize, prefix: &str, depth: usize, total: f64) {
+    if depth == 0 {
+        return;
+    }
+    // Sorted largest first, so the small ones are a tail
+    let children = tree.sorted_children(id, SortKey::Size);
+    let shown = children
+        .iter()
+        .take_while(|child| *tree.node(**child).size() as f64 / total >= TREE_MIN_SHARE)
+        .count();
+    let (large, small) = children.split_at(shown);
+
+    for (idx, child) in large.iter().enumerate() {
+        let last = idx + 1 == large.len() && small.is_empty();
+        let (branch, indent) = if last {
+            ("└── ", "    ")
+        } else {
+            ("├── ", "│   ")
+        };
+        let node = tree.node(*child);
+        match node.kind() {
+            NodeKind::Dir => {
+                print_tree_line(
+                    &format!("{}{}{}/", prefix, branch, node.name()),
+                    *node.size(),
+                    total,
+                    &format!("{} files", utils::format_number(node.file_count())),
+                );
+                print_tree_children(
+                    tree,
+                    *child,
+                    &format!("{}{}", prefix, indent),
+                    depth - 1,
+                    total,
+                );
+            }
+            NodeKind::File => print_tree_line(
+                &format!("{}{}{}", prefix, branch, node.name()),
+                *node.size(),
+                total,
+                "",
+            ),
+        }
+    }
+
+    if !small.is_empty() {
+        let size = small.iter().map(|child| *tree.node(*child).size()).sum();
+        print_tree_line(
+            &format!("{}└── (others)", prefix),
+            size,
+            total,
+            &format!("{} entries", utils::format_number(&(small.len() as u64))),
+        );
+    }
+}
+
+fn print_tree_line(name: &str, size: u64, total: f64, note: &str) {
+    // Box-drawing prefixes are multi-byte, so truncate by characters
+    let name = if name.chars().count() > TREE_NAME_WIDTH {
+        let kept: String = name.chars().take(TREE_NAME_WIDTH - 3).collect();
+        format!("{}...", kept)
+    } else {
+        name.to_string()
+    };
+    let ratio = size as f64 / total;
+    let line = format!(
+        "{:<width$} {:>10} {:>5.1}%  [{:<20}] {}",
+        name,
+        utils::format_size(&size),
+        ratio * 100.0,
+        "#".repeat((ratio * 20.0).round() as usize),
+        note,
+        width = TREE_NAME_WIDTH
+    );
+    println!("{}", line.trim_end());
+}
+
+fn display_owner_breakdown(breakdown: &model::OwnerBreakdown, usage: SizeMetric) {
+    let print_usages = |usages: &[model::OwnerUsage]| {
+        let total = usages.iter().map(|owner| *owner.size()).sum::<u64>().max(1) as f64;
+        for owner in usages {
+            let name = match owner.name() {
+                Some(name) => format!("{} ({})", name, owner.id()),
+                None => owner.id().to_string(),
+            };
+            println!(
+                "  {:<20} {:>10} {:>5.1}%  {} files",
+                name,
+                utils::format_size(owner.size()),
+                *owner.size() as f64 / total * 100.0,
+                utils::format_number(owner.count())
+            );
+            for folder in owner.largest_folders() {
+                let path_str = folder.path().display().to_string();
+                println!(
+                    "      {} {}",
+                    utils::truncate_path(&path_str, 56),
+                    format_usage(usage, folder.size(), folder.disk_usage())
+                );
+            }
+        }
+    };
+    println!("\nUsage by Owner:");
+    print_usages(breakdown.users());
+    println!("\nUsage by Group:");
+    print_usages(breakdown.groups());
+}
+
+fn display_age_histogram(histogram: &model::AgeHistogram) {
+    let total = histogram
+        .buckets()
+        .iter()
+        .map(|bucket| *bucket.size())
+        .sum::<u64>()
+        .max(1) as f64;
+    println!("\nAge Histogram ({}):", histogram.field().as_str());
+    let mut previous = None;
+    for bucket in histogram.buckets() {
+        let label = match (previous, bucket.max_age()) {
+            (None, Some(max_age)) => format!("< {}", utils::format_age(max_age)),
+            (Some(min_age), Some(max_age)) => format!(
+                "{}-{}",
+                utils::format_age(min_age),
+                utils::format_age(max_age)
+            ),
+            (Some(min_age), None) => format!("> {}", utils::format_age(min_age)),
+            (None, None) => "all".to_string(),
+        };
+        let ratio = *bucket.size() as f64 / total;
+        println!(
+            "  {:<10} {:>10} {:>5.1}%  [{:<20}] {} files",
+            label,
+            utils::format_size(bucket.size()),
+            ratio * 100.0,
+            "#".repeat((ratio * 20.0).round() as usize),
+            utils::format_number(bucket.count())
+        );
+        previous = bucket.max_age().as_ref().or(previous);
+    }
+
+    if let Some(threshold) = previous.filter(|_| !histogram.stale_files().is_empty()) {
+        println!(
+            "\nLargest Stale Files (older than {}):",
+            utils::format_age(threshold)
+        );
+        for (idx, file) in histogram.stale_files().iter().enumerate() {
+            let path_str = file.path().display().to_string();
+            println!(
+                "{}. {} {}",
+                idx + 1,
+                utils::truncate_path(&path_str, 60),
+                utils::format_size(file.size())
+            );
+        }
+    }
+}
+
+fn display_mount_points(mount_points: &[model::MountPoint]) {
+    if mount_points.is_empty() {
+        return;
+    }
+    println!("\nMount Points:");
+    for (idx, mount_point) in mount_points.iter().enumerate() {
+        let prefix = if idx + 1 == mount_points.len() { "└──" } else { "├──" };
+        println!(
+            "{} {} ({})",
+            prefix,
+            mount_point.path().display(),
+            if mount_point.included() { "included" } else { "skipped" }
+        );
+    }
+}
+
+// "1.2 GB (600.0 MB on disk)", with the ranking metric first
+fn format_usage(metric: SizeMetric, apparent: &u64, disk: &u64) -> String {
+    match metric {
+        SizeMetric::Disk => format!(
+            "{} on disk ({} apparent)",
+            utils::format_size(disk),
+            utils::format_size(apparent)
+        ),
+        _ => format!(
+            "{} ({} on disk)",
+            utils::format_size(apparent),
+            utils::format_size(disk)
+        ),
+    }
+}
+
+/// Prints search results. Text and JSON Lines output stream matches as they
+/// are found unless `sort` asks for them in path order; a JSON document is
+/// always sorted. `status` is cleared before anything is printed.
+pub fn display_search_result(
+    options: model::SearchOptions,
+    format: OutputFormat,
+    sort: bool,
+    status: Option<&StatusLine>,
+) -> Result<(), Box<dyn Error>> {
+    match format {
+        OutputFormat::Text if options.content_pattern().is_some() => {
+            display_content_search(&options, sort, status)
+        }
+        OutputFormat::Text => display_simple_search(&options, sort, status),
+        OutputFormat::Json => {
+            let result = file_finder(&options);
+            clear_status(status);
+            print_json(
+                &SearchReport {
+                    path: options.path().clone(),
+                    result: &result?,
+                },
+                true,
+            )
+        }
+        OutputFormat::Jsonl => {
+            // The walk can't be interrupted from here, so keep the first
+            // write error (e.g. a closed pipe) and report it at the end
+            let mut written = Ok(());
+            let result = for_each_match(&options, sort, status, |file| {
+                if written.is_ok() {
+                    written = print_json(&SearchEvent::Match(file), false);
+                }
+            })?;
+            written?;
+            print_json(
+                &SearchEvent::Summary {
+                    path: options.path().clone(),
+                    files_found: *result.files_found(),
+                    files_searched: *result.file_searched(),
+                    binary_skipped: *result.binary_skipped(),
+                    total_size: *result.total_size(),
+                    search_time_ms: result.search_time().as_millis() as u64,
+                },
+                false,
+            )
+        }
+    }
+}
+
+// Hands each match to `on_match` as it is found, or all of them sorted by
+// path once the search is done; the status line is cleared before each one
+fn for_each_match(
+    options: &model::SearchOptions,
+    sort: bool,
+    status: Option<&StatusLine>,
+    mut on_match: impl FnMut(&model::FileInfoSearch),
+) -> Result<model::SearchResult, AnalysisError> {
+    let result = if sort {
+        let result = file_finder(options);
+        clear_status(status);
+        let result = result?;
+        result.files_result().iter().for_each(&mut on_match);
+        Ok(result)
+    } else {
+        stream_files(options, |file| {
+            clear_status(status);
+            on_match(&file)
+        })
+    };
+    clear_status(status);
+    result
+}
+
+fn clear_status(status: Option<&StatusLine>) {
+    if let Some(status) = status {
+        status.clear();
+    }
+}
+
+fn print_json<T: Serialize>(value: &T, pretty: bool) -> Result<(), Box<dyn Error>> {
+    let mut stdout = io::stdout().lock();
+    if pretty {
+        serde_json::to_writer_pretty(&mut stdout, value)?;
     } else {
-        display_simple_search(&options, &result)
+        serde_json::to_writer(&mut stdout, value)?;
     }
+    writeln!(stdout)?;
+    Ok(())
 }
 
 fn display_simple_search(
     options: &model::SearchOptions,
-    result: &model::SearchResult,
+    sort: bool,
+    status: Option<&StatusLine>,
 ) -> Result<(), Box<dyn Error>> {
-    println!(
-        "🔍 Search Results ({} matches):",
-        result.files_result().len()
-    );
+    println!("🔍 Search Results:");
 
     if !options.name_pattern().is_empty() {
         for pattern in options.name_pattern() {
@@ -110,20 +748,31 @@ fn display_simple_search(
     if let Some(max) = options.max_size() {
         println!("└── Max size: {}", utils::format_size(max));
     }
+    if let Some(filter) = options.filter() {
+        println!("└── Where: {}", filter.expression());
+    }
+    let show_types = options.file_types().as_slice() != [FileType::File];
+    if show_types {
+        let types: Vec<&str> = options.file_types().iter().map(|t| t.as_str()).collect();
+        println!("└── Types: {}", types.join(", "));
+    }
     println!();
 
-    for file in result.files_result() {
+    let result = for_each_match(options, sort, status, |file| {
         println!("{}", file.path().display());
+        if show_types {
+            println!("├── Type: {}", file.file_type().as_str());
+        }
         println!("├── Size: {}", utils::format_size(file.size()));
         println!(
             "└── Modified: {}",
             utils::format_datetime(file.modified_date())
         );
         println!();
-    }
+    })?;
 
     println!("📊 Summary:");
-    println!("├── Files found: {}", result.files_result().len());
+    println!("├── Files found: {}", result.files_found());
     println!(
         "├── Total size: {}",
         utils::format_size(result.total_size())
@@ -132,38 +781,193 @@ fn display_simple_search(
         "└── Search time: {:.1}s",
         result.search_time().as_secs_f32()
     );
+    display_mount_points(result.mount_points());
 
     Ok(())
 }
 
 fn display_content_search(
-    _options: &model::SearchOptions,
-    result: &model::SearchResult,
+    options: &model::SearchOptions,
+    sort: bool,
+    status: Option<&StatusLine>,
 ) -> Result<(), Box<dyn Error>> {
+    println!("🔍 Content Search Results:");
+    let match_options = options.content_match_options();
+    let mut modes = vec![if match_options.regex() { "regex" } else { "fixed string" }];
+    if match_options.case_insensitive() {
+        modes.push("ignore case");
+    }
+    if match_options.whole_word() {
+        modes.push("whole word");
+    }
+    if match_options.binary() {
+        modes.push("binary");
+    }
     println!(
-        "🔍 Content Search Results ({} matches):\n",
-        result.files_result().len()
+        "└── Pattern: \"{}\" ({})\n",
+        options.content_pattern().as_deref().unwrap_or_default(),
+        modes.join(", ")
     );
 
-    for file in result.files_result() {
-        if let Some((line_num, content)) = file.content() {
-            let path_str = file.path().display().to_string();
+    let highlight = io::stdout().is_terminal();
+    let mut total_matches = 0;
+    let result = for_each_match(options, sort, status, |file| {
+        total_matches += file.match_count();
+        println!("{} ({} matches)", file.path().display(), file.match_count());
+        if file.binary() {
+            let offsets: Vec<String> = file
+                .binary_offsets()
+                .iter()
+                .map(|offset| offset.to_string())
+                .collect();
+            println!("  binary file matches at offsets {}\n", offsets.join(", "));
+            return;
+        }
+
+        let mut last_line = 0;
+        for line_match in file.matches() {
+            let first_line = line_match
+                .context_before()
+                .first()
+                .map(|context| context.line_number())
+                .unwrap_or(line_match.line_number());
+            if last_line > 0 && first_line > last_line + 1 {
+                println!("  --");
+            }
+
+            for context in line_match.context_before() {
+                println!("  {}-  {}", context.line_number(), context.line());
+            }
+            let content = if highlight {
+                utils::highlight_span(
+                    line_match.line(),
+                    line_match.match_start(),
+                    line_match.match_end(),
+                )
+            } else {
+                line_match.line().to_string()
+            };
             println!(
-                "{}:{} - {}",
-                utils::truncate_path(&path_str, 30),
-                line_num,
+                "  {}:{}: {}",
+                line_match.line_number(),
+                line_match.column(),
                 content
             );
+            for context in line_match.context_after() {
+                println!("  {}-  {}", context.line_number(), context.line());
+            }
+
+            last_line = line_match
+                .context_after()
+                .last()
+                .map(|context| context.line_number())
+                .unwrap_or(line_match.line_number());
         }
-    }
+        println!();
+    })?;
 
-    println!("\n📊 Summary:");
+    println!("📊 Summary:");
     println!("├── Files searched: {}", result.file_searched());
-    println!("├── Matches found: {}", result.files_result().len());
+    println!("├── Files matched: {}", result.files_found());
+    println!("├── Matches found: {}", total_matches);
+    if *result.binary_skipped() > 0 {
+        println!(
+            "├── Binary files skipped: {} (use --binary to search them)",
+            result.binary_skipped()
+        );
+    }
     println!(
         "└── Search time: {:.1}s",
         result.search_time().as_secs_f32()
     );
+    display_mount_points(result.mount_points());
+
+    Ok(())
+}
+
+pub fn display_snapshot_diff(
+    options: model::DiffOptions,
+    format: OutputFormat,
+) -> Result<(), Box<dyn Error>> {
+    let diff = snapshot_diff(&options)?;
+    if diff.roots_differ() {
+        eprintln!(
+            "Warning: comparing snapshots of different directories ({} and {}); \
+             paths are matched relative to each",
+            diff.old_root().display(),
+            diff.new_root().display()
+        );
+    }
+    match format {
+        OutputFormat::Text => display_diff_text(&options, &diff),
+        OutputFormat::Json => print_json(&diff, true),
+        OutputFormat::Jsonl => print_json(&diff, false),
+    }
+}
+
+fn display_diff_text(
+    options: &model::DiffOptions,
+    diff: &model::SnapshotDiff,
+) -> Result<(), Box<dyn Error>> {
+    println!("📊 Snapshot Diff Report");
+    println!("📂 Old: {}", options.old().display());
+    println!("📂 New: {}\n", options.new_path().display());
+    println!("Summary:");
+    println!(
+        "├── Total size: {} → {} ({})",
+        utils::format_size(diff.old_total_size()),
+        utils::format_size(diff.new_total_size()),
+        utils::format_size_delta(*diff.new_total_size() as i64 - *diff.old_total_size() as i64)
+    );
+    println!("├── Added: {}", diff.added().len());
+    println!("├── Removed: {}", diff.removed().len());
+    println!("└── Modified: {}", diff.modified().len());
 
+    println!("\nLargest Folder Changes:");
+    for (idx, folder) in diff.folder_deltas().iter().take(options.top_n()).enumerate() {
+        println!(
+            "{}. {} {} ({} → {})",
+            idx + 1,
+            utils::truncate_path(&display_relative(folder.path()), 60),
+            utils::format_size_delta(folder.delta()),
+            utils::format_size(folder.old_size()),
+            utils::format_size(folder.new_size())
+        );
+    }
+
+    println!("\nLargest Growth:");
+    for (idx, file) in diff.grown().iter().take(options.top_n()).enumerate() {
+        println!(
+            "{}. {} {} ({} → {})",
+            idx + 1,
+            utils::truncate_path(&display_relative(file.path()), 60),
+            utils::format_size_delta(file.delta()),
+            utils::format_size(&file.old_size().unwrap_or_default()),
+            utils::format_size(&file.new_size().unwrap_or_default())
+        );
+    }
+
+    for (title, changes) in [("Added", diff.added()), ("Removed", diff.removed())] {
+        println!("\n{} Files:", title);
+        for (idx, file) in changes.iter().take(options.top_n()).enumerate() {
+            println!(
+                "{}. {} {}",
+                idx + 1,
+                utils::truncate_path(&display_relative(file.path()), 60),
+                utils::format_size_delta(file.delta())
+            );
+        }
+        if changes.len() > options.top_n() {
+            println!("   ... and {} more", changes.len() - options.top_n());
+        }
+    }
     Ok(())
 }
+
+fn display_relative(path: &std::path::Path) -> String {
+    if path.as_os_str().is_empty() {
+        ".".to_string()
+    } else {
+        path.display().to_string()
+    }
+}
\ No newline at end of file
@@ -0,0 +1,313 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use rayon::{prelude::*, ThreadPool, ThreadPoolBuilder};
+use sha2::{Digest, Sha256};
+use xxhash_rust::xxh3::{xxh3_64, Xxh3Default};
+
+use crate::cancel::Interrupt;
+use crate::model::{DuplicateGroup, HashAlgorithm, ProgressCallback};
+use crate::progress::HashProgress;
+
+// Bytes hashed from each end of a file by the prefilter stage
+const PARTIAL_HASH_SIZE: usize = 4 * 1024;
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Workers for the hashing stages of one scan: rayon's global pool for
+/// `threads == 0` (one per core), otherwise a pool of `threads` built on
+/// first use and shared by every stage.
+pub struct HashPool {
+    threads: usize,
+    pool: OnceLock<Option<ThreadPool>>,
+}
+
+impl HashPool {
+    pub fn new(threads: usize) -> Self {
+        HashPool {
+            threads,
+            pool: OnceLock::new(),
+        }
+    }
+
+    fn install<T: Send>(&self, run: impl FnOnce() -> T + Send) -> T {
+        let pool = self.pool.get_or_init(|| {
+            // Falls back to the global pool if the threads can't be spawned
+            (self.threads != 0)
+                .then(|| ThreadPoolBuilder::new().num_threads(self.threads).build().ok())
+                .flatten()
+        });
+        match pool {
+            Some(pool) => pool.install(run),
+            None => run(),
+        }
+    }
+}
+
+/// Finds groups of identical files among files of equal size.
+///
+/// Candidates go through three stages: grouping by size, a cheap hash of the
+/// first and last few KiB, and a full hash of whatever still collides. Both
+/// hashing stages run on `pool`. The full hash stage reports its progress to
+/// `progress`. Once `interrupt` fires, the remaining files are skipped.
+/// Empty files are left out: they are all alike and free nothing.
+pub fn find_duplicates(
+    size_groups: HashMap<u64, Vec<PathBuf>>,
+    algorithm: HashAlgorithm,
+    pool: &HashPool,
+    progress: Option<&ProgressCallback>,
+    interrupt: &Interrupt,
+) -> Vec<DuplicateGroup> {
+    let run = || {
+        let candidates: Vec<(u64, PathBuf)> = size_groups
+            .into_iter()
+            .filter(|(size, files)| *size > 0 && files.len() > 1)
+            .flat_map(|(size, files)| files.into_iter().map(move |path| (size, path)))
+            .collect();
+
+        let candidates = prefilter_by_partial_hash(candidates, interrupt);
+        group_by_full_hash(candidates, algorithm, progress, interrupt)
+    };
+
+    let mut duplicates = pool.install(run);
+    duplicates.sort_by(|a, b| b.size().cmp(a.size()).then_with(|| a.files().cmp(b.files())));
+    duplicates
+}
+
+/// Hashes every `(path, size)` with `algorithm` on `pool`, `None` for files
+/// that can't be read or were skipped after `interrupt` fired, reporting
+/// progress to `progress`.
+pub fn hash_files(
+    files: &[(&Path, u64)],
+    algorithm: HashAlgorithm,
+    pool: &HashPool,
+    progress: Option<&ProgressCallback>,
+    interrupt: &Interrupt,
+) -> Vec<Option<String>> {
+    let progress = HashProgress::new(
+        progress,
+        files.len() as u64,
+        files.iter().map(|(_, size)| size).sum(),
+    );
+    let hashes = pool.install(|| {
+        files
+            .par_iter()
+            .map(|(path, size)| {
+                if interrupt.check() {
+                    return None;
+                }
+                let hash = calculate_file_hash(path, algorithm).ok();
+                progress.add(*size);
+                hash
+            })
+            .collect()
+    });
+    progress.finish();
+    hashes
+}
+
+fn prefilter_by_partial_hash(
+    candidates: Vec<(u64, PathBuf)>,
+    interrupt: &Interrupt,
+) -> Vec<(u64, PathBuf)> {
+    // Small files are read whole by the next stage anyway
+    let (small, large): (Vec<_>, Vec<_>) = candidates
+        .into_iter()
+        .partition(|(size, _)| *size <= (PARTIAL_HASH_SIZE * 2) as u64);
+
+    let hashed: Vec<((u64, u64), PathBuf)> = large
+        .into_par_iter()
+        .filter_map(|(size, path)| {
+            if interrupt.check() {
+                return None;
+            }
+            calculate_partial_hash(&path)
+                .ok()
+                .map(|hash| ((size, hash), path))
+        })
+        .collect();
+
+    let mut survivors = small;
+    survivors.extend(
+        group_colliding(hashed)
+            .into_iter()
+            .flat_map(|((size, _), paths)| paths.into_iter().map(move |path| (size, path))),
+    );
+    survivors
+}
+
+fn group_by_full_hash(
+    candidates: Vec<(u64, PathBuf)>,
+    algorithm: HashAlgorithm,
+    progress: Option<&ProgressCallback>,
+    interrupt: &Interrupt,
+) -> Vec<DuplicateGroup> {
+    let progress = HashProgress::new(
+        progress,
+        candidates.len() as u64,
+        candidates.iter().map(|(size, _)| size).sum(),
+    );
+    let hashed: Vec<((u64, String), PathBuf)> = candidates
+        .into_par_iter()
+        .filter_map(|(size, path)| {
+            if interrupt.check() {
+                return None;
+            }
+            let hash = calculate_file_hash(&path, algorithm).ok();
+            progress.add(size);
+            hash.map(|hash| ((size, hash), path))
+        })
+        .collect();
+    progress.finish();
+
+    group_colliding(hashed)
+        .into_iter()
+        .map(|((size, hash), paths)| DuplicateGroup::new(paths, size, hash))
+        .collect()
+}
+
+/// Groups paths by key, keeping only keys shared by more than one path.
+fn group_colliding<K: std::hash::Hash + Eq>(hashed: Vec<(K, PathBuf)>) -> Vec<(K, Vec<PathBuf>)> {
+    let mut groups: HashMap<K, Vec<PathBuf>> = HashMap::new();
+    for (key, path) in hashed {
+        groups.entry(key).or_default().push(path);
+    }
+    groups
+        .into_iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .map(|(key, mut paths)| {
+            paths.sort();
+            (key, paths)
+        })
+        .collect()
+}
+
+fn calculate_partial_hash(path: &Path) -> io::Result<u64> {
+    let mut file = File::open(path)?;
+    let mut buffer = [0; PARTIAL_HASH_SIZE * 2];
+
+    file.read_exact(&mut buffer[..PARTIAL_HASH_SIZE])?;
+    file.seek(SeekFrom::End(-(PARTIAL_HASH_SIZE as i64)))?;
+    file.read_exact(&mut buffer[PARTIAL_HASH_SIZE..])?;
+
+    Ok(xxh3_64(&buffer))
+}
+
+fn calculate_file_hash(path: &Path, algorithm: HashAlgorithm) -> io::Result<String> {
+    let mut file = File::open(path)?;
+
+    match algorithm {
+        HashAlgorithm::Sha256 => {
+            let mut hasher = Sha256::new();
+            read_chunks(&mut file, |chunk| hasher.update(chunk))?;
+            Ok(format!("{:x}", hasher.finalize()))
+        }
+        HashAlgorithm::Blake3 => {
+            let mut hasher = blake3::Hasher::new();
+            read_chunks(&mut file, |chunk| {
+                hasher.update(chunk);
+            })?;
+            Ok(hasher.finalize().to_hex().to_string())
+        }
+        HashAlgorithm::Xxh3 => {
+            let mut hasher = Xxh3Default::new();
+            read_chunks(&mut file, |chunk| hasher.update(chunk))?;
+            Ok(format!("{:032x}", hasher.digest128()))
+        }
+    }
+}
+
+fn read_chunks(reader: &mut impl Read, mut consume: impl FnMut(&[u8])) -> io::Result<()> {
+    let mut buffer = vec![0; READ_BUFFER_SIZE];
+    loop {
+        let count = reader.read(&mut buffer)?;
+        if count == 0 {
+            break;
+        }
+        consume(&buffer[..count]);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    // Duplicate groups among `files`, as file names
+    fn duplicates(files: &[(&str, Vec<u8>)]) -> Vec<Vec<String>> {
+        let temp = tempfile::tempdir().unwrap();
+        let mut size_groups: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+        for (name, content) in files {
+            let path = temp.path().join(name);
+            fs::write(&path, content).unwrap();
+            size_groups.entry(content.len() as u64).or_default().push(path);
+        }
+        let groups = find_duplicates(
+            size_groups,
+            HashAlgorithm::Xxh3,
+            &HashPool::new(2),
+            None,
+            &Interrupt::new(None, None),
+        );
+        groups
+            .iter()
+            .map(|group| {
+                group
+                    .files()
+                    .iter()
+                    .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+                    .collect()
+            })
+            .collect()
+    }
+
+    // Larger than both partial hash windows, `tail` bytes at the end
+    fn large(tail: &[u8]) -> Vec<u8> {
+        let mut content = vec![b'x'; PARTIAL_HASH_SIZE * 4];
+        let start = content.len() - tail.len();
+        content[start..].copy_from_slice(tail);
+        content
+    }
+
+    #[test]
+    fn identical_files_are_grouped_by_size_then_hash() {
+        let groups = duplicates(&[
+            ("a", large(b"same")),
+            ("b", large(b"same")),
+            ("c", b"small".to_vec()),
+            ("d", b"small".to_vec()),
+            ("e", b"other".to_vec()),
+        ]);
+        // Largest first, files in path order
+        assert_eq!(groups, [vec!["a", "b"], vec!["c", "d"]]);
+    }
+
+    #[test]
+    fn same_size_and_prefix_but_different_tail_are_not_duplicates() {
+        // Dropped by the partial hash
+        assert!(duplicates(&[("a", large(b"tail1")), ("b", large(b"tail2"))]).is_empty());
+
+        // Same first and last KiB, so only the full hash tells them apart
+        let mut middle = large(b"");
+        middle[PARTIAL_HASH_SIZE * 2] = b'y';
+        assert!(duplicates(&[("a", large(b"")), ("b", middle)]).is_empty());
+    }
+
+    #[test]
+    fn empty_and_single_files_are_not_groups() {
+        let groups = duplicates(&[
+            ("empty1", Vec::new()),
+            ("empty2", Vec::new()),
+            ("alone", large(b"unique size!")),
+            ("copy1", b"copy".to_vec()),
+            ("copy2", b"copy".to_vec()),
+        ]);
+        assert_eq!(groups, [vec!["copy1", "copy2"]]);
+    }
+}
@@ -2,11 +2,19 @@ use std::path::PathBuf;
 use std::error::Error;
 use std::fmt;
 
+use crate::model::StopReason;
+
 #[derive(Debug)]
 #[warn(dead_code)]
+#[non_exhaustive]
 pub enum AnalysisError {
     IoError(std::io::Error),
     PathNotFound(PathBuf),
+    InvalidSnapshot(PathBuf, String),
+    ExportError(PathBuf, String),
+    InvalidOptions(OptionsError),
+    // A scan whose result is useless when partial, like a live diff, stopped early
+    Interrupted(StopReason),
 }
 
 impl fmt::Display for AnalysisError {
@@ -14,7 +22,57 @@ impl fmt::Display for AnalysisError {
         match self {
             AnalysisError::IoError(e) => write!(f, "IO Error: {}", e),
             AnalysisError::PathNotFound(path) => write!(f, "Path not found: {}", path.display()),
+            AnalysisError::InvalidSnapshot(path, reason) => {
+                write!(f, "Invalid snapshot {}: {}", path.display(), reason)
+            }
+            AnalysisError::ExportError(path, reason) => {
+                write!(f, "Cannot export to {}: {}", path.display(), reason)
+            }
+            AnalysisError::InvalidOptions(e) => write!(f, "{}", e),
+            AnalysisError::Interrupted(reason) => {
+                write!(f, "Scan {} before it covered the whole directory", reason.describe())
+            }
         }
     }
 }
 impl Error for AnalysisError {}
+
+/// Why `AnalyzeOptionsBuilder::build`, `SearchOptionsBuilder::build` or
+/// `DiffOptionsBuilder::build` rejected its settings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum OptionsError {
+    InvalidPattern { pattern: String, reason: String },
+    InvalidSize(String),
+    InvalidRegex { pattern: String, reason: String },
+    InvalidFilter(String),
+    Conflict(&'static str),
+    ZeroTopN,
+    MissingHashAlgorithm,
+}
+
+impl fmt::Display for OptionsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OptionsError::InvalidPattern { pattern, reason } => {
+                write!(f, "Invalid pattern '{}': {}", pattern, reason)
+            }
+            OptionsError::InvalidSize(size) => write!(
+                f,
+                "Invalid size '{}': expected bytes or a number followed by KB, MB, GB or TB",
+                size
+            ),
+            OptionsError::InvalidRegex { pattern, reason } => {
+                write!(f, "Invalid content pattern '{}': {}", pattern, reason)
+            }
+            OptionsError::InvalidFilter(reason) => write!(f, "{}", reason),
+            OptionsError::Conflict(reason) => write!(f, "Conflicting options: {}", reason),
+            OptionsError::ZeroTopN => write!(f, "Invalid top N: must be at least 1"),
+            OptionsError::MissingHashAlgorithm => write!(
+                f,
+                "Conflicting options: snapshot and inventory hashes need a hash algorithm"
+            ),
+        }
+    }
+}
+impl Error for OptionsError {}
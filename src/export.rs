@@ -0,0 +1,200 @@
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+    path::Path,
+};
+
+use rusqlite::{params, Connection};
+
+use crate::error::AnalysisError;
+use crate::model::{InventoryEntry, StopReason};
+use crate::{owners, utils};
+
+const HEADER: [&str; 11] = [
+    "path", "type", "size", "blocks", "mtime", "uid", "user", "gid", "group", "mode", "hash",
+];
+
+// One inventory row as text, in HEADER order; missing values are empty
+struct Row {
+    path: String,
+    file_type: String,
+    size: u64,
+    blocks: u64,
+    mtime: Option<String>,
+    uid: Option<u32>,
+    user: Option<String>,
+    gid: Option<u32>,
+    group: Option<String>,
+    mode: Option<String>,
+    hash: Option<String>,
+}
+
+struct Names {
+    users: HashMap<u32, String>,
+    groups: HashMap<u32, String>,
+}
+
+impl Names {
+    fn load() -> Self {
+        Names {
+            users: owners::user_names(),
+            groups: owners::group_names(),
+        }
+    }
+
+    fn row(&self, entry: &InventoryEntry) -> Row {
+        let (uid, gid) = entry.owner().unzip();
+        Row {
+            path: entry.path().display().to_string(),
+            file_type: entry
+                .file_type()
+                .map_or("unknown", |file_type| file_type.as_str())
+                .to_string(),
+            size: *entry.size(),
+            blocks: entry.blocks(),
+            mtime: entry.modified().as_ref().map(utils::format_rfc3339),
+            uid,
+            user: uid.and_then(|uid| self.users.get(&uid).cloned()),
+            gid,
+            group: gid.and_then(|gid| self.groups.get(&gid).cloned()),
+            mode: entry.permissions().map(|mode| format!("{:04o}", mode)),
+            hash: entry.hash().clone(),
+        }
+    }
+}
+
+/// Writes the inventory as CSV with a header row.
+///
+/// When the scan stopped early (`incomplete`), the last line is a comment
+/// such as `# incomplete: timed_out`, after the rows scanned so far.
+pub fn export_csv(
+    inventory: &[InventoryEntry],
+    incomplete: Option<StopReason>,
+    path: &Path,
+) -> Result<(), AnalysisError> {
+    let export_error = |e: csv::Error| AnalysisError::ExportError(path.to_path_buf(), e.to_string());
+    let mut writer = csv::Writer::from_path(path).map_err(export_error)?;
+    writer.write_record(HEADER).map_err(export_error)?;
+
+    let names = Names::load();
+    for entry in inventory {
+        let row = names.row(entry);
+        let optional = |value: Option<String>| value.unwrap_or_default();
+        writer
+            .write_record([
+                row.path,
+                row.file_type,
+                row.size.to_string(),
+                row.blocks.to_string(),
+                optional(row.mtime),
+                optional(row.uid.map(|uid| uid.to_string())),
+                optional(row.user),
+                optional(row.gid.map(|gid| gid.to_string())),
+                optional(row.group),
+                optional(row.mode),
+                optional(row.hash),
+            ])
+            .map_err(export_error)?;
+    }
+    let io_error = |e: io::Error| AnalysisError::ExportError(path.to_path_buf(), e.to_string());
+    let mut file = writer.into_inner().map_err(|e| io_error(e.into_error()))?;
+    if let Some(reason) = incomplete {
+        writeln!(file, "# incomplete: {}", reason.as_str()).map_err(io_error)?;
+    }
+    file.flush().map_err(io_error)
+}
+
+/// Writes the inventory into a `files` table, replacing any previous one
+/// and leaving other tables in the database alone.
+///
+/// A one-row `scan_info` table is replaced along with it; its `incomplete`
+/// column names why the scan stopped early, or is NULL.
+pub fn export_sqlite(
+    inventory: &[InventoryEntry],
+    incomplete: Option<StopReason>,
+    path: &Path,
+) -> Result<(), AnalysisError> {
+    let export_error =
+        |e: rusqlite::Error| AnalysisError::ExportError(path.to_path_buf(), e.to_string());
+    let mut connection = Connection::open(path).map_err(export_error)?;
+    let transaction = connection.transaction().map_err(export_error)?;
+    transaction
+        .execute_batch(
+            "DROP TABLE IF EXISTS files;
+             CREATE TABLE files (
+                 path TEXT PRIMARY KEY,
+                 type TEXT NOT NULL,
+                 size INTEGER NOT NULL,
+                 blocks INTEGER NOT NULL,
+                 mtime TEXT,
+                 uid INTEGER,
+                 user TEXT,
+                 gid INTEGER,
+                 \"group\" TEXT,
+                 mode TEXT,
+                 hash TEXT
+             );
+             DROP TABLE IF EXISTS scan_info;
+             CREATE TABLE scan_info (incomplete TEXT);",
+        )
+        .map_err(export_error)?;
+    transaction
+        .execute(
+            "INSERT INTO scan_info VALUES (?1)",
+            params![incomplete.map(|reason| reason.as_str())],
+        )
+        .map_err(export_error)?;
+    {
+        let mut insert = transaction
+            .prepare("INSERT INTO files VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)")
+            .map_err(export_error)?;
+        let names = Names::load();
+        for entry in inventory {
+            let row = names.row(entry);
+            insert
+                .execute(params![
+                    row.path,
+                    row.file_type,
+                    row.size as i64,
+                    row.blocks as i64,
+                    row.mtime,
+                    row.uid,
+                    row.user,
+                    row.gid,
+                    row.group,
+                    row.mode,
+                    row.hash,
+                ])
+                .map_err(export_error)?;
+        }
+    }
+    transaction.commit().map_err(export_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn partial_csv_ends_with_its_reason() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("a.txt");
+        fs::write(&file, "abc").unwrap();
+        let metadata = fs::metadata(&file).unwrap();
+        let inventory = [InventoryEntry::from_metadata(file.clone(), &metadata)];
+        let csv = dir.path().join("inventory.csv");
+
+        export_csv(&inventory, Some(StopReason::TimedOut), &csv).unwrap();
+        let text = fs::read_to_string(&csv).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("path,type,size"));
+        assert!(lines[1].starts_with(&format!("{},file,3,", file.display())));
+        assert_eq!(lines[2], "# incomplete: timed_out");
+
+        export_csv(&inventory, None, &csv).unwrap();
+        assert_eq!(fs::read_to_string(&csv).unwrap().lines().count(), 2);
+    }
+}
@@ -0,0 +1,461 @@
+use std::{
+    fs::Metadata,
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+use glob::Pattern;
+
+use crate::utils;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+    NotEqual,
+}
+
+impl Comparison {
+    fn holds<T: PartialOrd>(self, left: T, right: T) -> bool {
+        match self {
+            Comparison::Less => left < right,
+            Comparison::LessOrEqual => left <= right,
+            Comparison::Greater => left > right,
+            Comparison::GreaterOrEqual => left >= right,
+            Comparison::Equal => left == right,
+            Comparison::NotEqual => left != right,
+        }
+    }
+}
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Clone)]
+pub enum Node {
+    And(Box<Node>, Box<Node>),
+    Or(Box<Node>, Box<Node>),
+    Not(Box<Node>),
+    Name(Pattern),
+    Path(Pattern),
+    Ext(String),
+    Size(Comparison, u64),
+    Modified(Comparison, SystemTime),
+    // Modified during the day starting at the given midnight
+    ModifiedOn(SystemTime),
+}
+
+/// A parsed `--where` expression.
+///
+/// Terms are `name:GLOB` (file name), `path:GLOB` (path relative to the
+/// search root, or the full path), `ext:EXT`, `size OP SIZE` and
+/// `mtime OP AGE|DATE`, combined with `and`, `or`, `not` and parentheses.
+/// `not` binds tightest, then `and`, then `or`.
+///
+/// `mtime` always compares timestamps: an age stands for that long ago, so
+/// `mtime<30d` is modified before 30 days ago, and a date for that whole
+/// day, so `mtime<=2024-01-01` includes January 1st. Ages only take `<`,
+/// `<=`, `>` and `>=`.
+#[derive(Debug, Clone)]
+pub struct Filter {
+    expression: String,
+    root: Node,
+}
+
+impl Filter {
+    pub fn parse(expression: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            input: expression,
+            position: 0,
+            now: SystemTime::now(),
+        };
+        let root = parser.parse_or()?;
+        parser.skip_whitespace();
+        if parser.position < expression.len() {
+            return Err(parser.error("unexpected input"));
+        }
+        Ok(Filter {
+            expression: expression.to_string(),
+            root,
+        })
+    }
+
+    pub fn expression(&self) -> &str {
+        &self.expression
+    }
+
+    pub fn matches(&self, root: &Path, path: &Path, metadata: &Metadata) -> bool {
+        evaluate(&self.root, root, path, metadata)
+    }
+}
+
+fn evaluate(node: &Node, root: &Path, path: &Path, metadata: &Metadata) -> bool {
+    match node {
+        Node::And(left, right) => {
+            evaluate(left, root, path, metadata) && evaluate(right, root, path, metadata)
+        }
+        Node::Or(left, right) => {
+            evaluate(left, root, path, metadata) || evaluate(right, root, path, metadata)
+        }
+        Node::Not(inner) => !evaluate(inner, root, path, metadata),
+        Node::Name(pattern) => path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| pattern.matches(name)),
+        Node::Path(pattern) => {
+            let relative = path.strip_prefix(root).unwrap_or(path);
+            pattern.matches_path(relative) || pattern.matches_path(path)
+        }
+        Node::Ext(ext) => path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| e.eq_ignore_ascii_case(ext)),
+        Node::Size(comparison, size) => comparison.holds(metadata.len(), *size),
+        Node::Modified(comparison, time) => metadata
+            .modified()
+            .is_ok_and(|modified| comparison.holds(modified, *time)),
+        Node::ModifiedOn(day) => metadata
+            .modified()
+            .is_ok_and(|modified| modified >= *day && modified < *day + DAY),
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    position: usize,
+    // Relative ages are resolved once, against the time of parsing
+    now: SystemTime,
+}
+
+impl<'a> Parser<'a> {
+    fn parse_or(&mut self) -> Result<Node, String> {
+        let mut node = self.parse_and()?;
+        while self.eat_keyword("or") {
+            node = Node::Or(Box::new(node), Box::new(self.parse_and()?));
+        }
+        Ok(node)
+    }
+
+    fn parse_and(&mut self) -> Result<Node, String> {
+        let mut node = self.parse_not()?;
+        while self.eat_keyword("and") {
+            node = Node::And(Box::new(node), Box::new(self.parse_not()?));
+        }
+        Ok(node)
+    }
+
+    fn parse_not(&mut self) -> Result<Node, String> {
+        if self.eat_keyword("not") {
+            return Ok(Node::Not(Box::new(self.parse_not()?)));
+        }
+        self.parse_term()
+    }
+
+    fn parse_term(&mut self) -> Result<Node, String> {
+        self.skip_whitespace();
+        if self.rest().starts_with('(') {
+            self.position += 1;
+            let node = self.parse_or()?;
+            self.skip_whitespace();
+            if !self.rest().starts_with(')') {
+                return Err(self.error("expected ')'"));
+            }
+            self.position += 1;
+            return Ok(node);
+        }
+
+        let start = self.position;
+        let field = self
+            .take_while(|c| c.is_ascii_alphabetic())
+            .to_ascii_lowercase();
+        match field.as_str() {
+            "name" | "path" | "ext" => {
+                if !self.rest().starts_with(':') {
+                    return Err(self.error(&format!("expected ':' after '{}'", field)));
+                }
+                self.position += 1;
+                let value = self.take_value()?;
+                match field.as_str() {
+                    "name" => Ok(Node::Name(self.pattern(&value)?)),
+                    "path" => Ok(Node::Path(self.pattern(&value)?)),
+                    _ => Ok(Node::Ext(value.trim_start_matches('.').to_string())),
+                }
+            }
+            "size" => {
+                let comparison = self.take_comparison()?;
+                let value = self.take_value()?;
+                let size = parse_size(&value).map_err(|e| self.error(&e))?;
+                Ok(Node::Size(comparison, size))
+            }
+            "mtime" => {
+                let comparison = self.take_comparison()?;
+                let value = self.take_value()?;
+                if let Ok(Some(day)) = utils::parse_date(Some(value.clone()), "mtime") {
+                    return Ok(modified_on_day(comparison, day));
+                }
+                let age = utils::parse_age(&value).map_err(|e| {
+                    self.error(&format!("{}; mtime also takes a YYYY-MM-DD date", e))
+                })?;
+                if matches!(comparison, Comparison::Equal | Comparison::NotEqual) {
+                    return Err(self.error("an age only compares with <, <=, > or >="));
+                }
+                let cutoff = self.now.checked_sub(age).unwrap_or(SystemTime::UNIX_EPOCH);
+                Ok(Node::Modified(comparison, cutoff))
+            }
+            "" => Err(self.error("expected a filter term")),
+            _ => {
+                self.position = start;
+                Err(self.error(&format!("unknown field '{}'", field)))
+            }
+        }
+    }
+
+    fn take_comparison(&mut self) -> Result<Comparison, String> {
+        self.skip_whitespace();
+        let operators = [
+            ("<=", Comparison::LessOrEqual),
+            (">=", Comparison::GreaterOrEqual),
+            ("!=", Comparison::NotEqual),
+            ("<", Comparison::Less),
+            (">", Comparison::Greater),
+            ("=", Comparison::Equal),
+        ];
+        for (symbol, comparison) in operators {
+            if self.rest().starts_with(symbol) {
+                self.position += symbol.len();
+                return Ok(comparison);
+            }
+        }
+        Err(self.error("expected a comparison (<, <=, >, >=, =, !=)"))
+    }
+
+    // A bare word up to whitespace or ')', or a double-quoted string
+    fn take_value(&mut self) -> Result<String, String> {
+        self.skip_whitespace();
+        if let Some(quoted) = self.rest().strip_prefix('"') {
+            let Some(end) = quoted.find('"') else {
+                return Err(self.error("unterminated quote"));
+            };
+            let value = quoted[..end].to_string();
+            self.position += end + 2;
+            return Ok(value);
+        }
+        let value = self.take_while(|c| !c.is_whitespace() && c != ')');
+        if value.is_empty() {
+            return Err(self.error("expected a value"));
+        }
+        Ok(value.to_string())
+    }
+
+    fn pattern(&self, value: &str) -> Result<Pattern, String> {
+        Pattern::new(value).map_err(|e| self.error(&format!("invalid pattern '{}': {}", value, e)))
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        self.skip_whitespace();
+        let rest = self.rest();
+        let matched = rest
+            .get(..keyword.len())
+            .is_some_and(|word| word.eq_ignore_ascii_case(keyword))
+            && !rest[keyword.len()..].starts_with(|c: char| c.is_ascii_alphanumeric());
+        if matched {
+            self.position += keyword.len();
+        }
+        matched
+    }
+
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> &'a str {
+        let start = self.position;
+        let length = self.rest().find(|c| !predicate(c)).unwrap_or(self.rest().len());
+        self.position += length;
+        &self.input[start..self.position]
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.take_while(char::is_whitespace);
+    }
+
+    fn rest(&self) -> &str {
+        &self.input[self.position..]
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!(
+            "Invalid --where expression at column {}: {}",
+            self.position + 1,
+            message
+        )
+    }
+}
+
+// Compares against the whole day starting at `day`
+fn modified_on_day(comparison: Comparison, day: SystemTime) -> Node {
+    let next_day = day + DAY;
+    match comparison {
+        Comparison::Less => Node::Modified(Comparison::Less, day),
+        Comparison::LessOrEqual => Node::Modified(Comparison::Less, next_day),
+        Comparison::Greater => Node::Modified(Comparison::GreaterOrEqual, next_day),
+        Comparison::GreaterOrEqual => Node::Modified(Comparison::GreaterOrEqual, day),
+        Comparison::Equal => Node::ModifiedOn(day),
+        Comparison::NotEqual => Node::Not(Box::new(Node::ModifiedOn(day))),
+    }
+}
+
+/// Parses sizes such as `512`, `10KB` or `1.5GB` (binary multiples, like
+/// `format_size`).
+fn parse_size(value: &str) -> Result<u64, String> {
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid size '{}'", value))?;
+    let multiplier: u64 = match unit.to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" => 1 << 10,
+        "M" | "MB" => 1 << 20,
+        "G" | "GB" => 1 << 30,
+        "T" | "TB" => 1 << 40,
+        _ => return Err(format!("unknown size unit '{}'", unit)),
+    };
+    Ok((number * multiplier as f64) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    fn parse(expression: &str) -> Node {
+        Filter::parse(expression).unwrap().root
+    }
+
+    fn error(expression: &str) -> String {
+        Filter::parse(expression).unwrap_err()
+    }
+
+    #[test]
+    fn not_binds_tighter_than_and_which_binds_tighter_than_or() {
+        let node = parse("not name:a and name:b or name:c");
+        let Node::Or(left, right) = node else {
+            panic!("expected or at the top: {:?}", node);
+        };
+        assert!(matches!(*right, Node::Name(_)));
+        let Node::And(left, right) = *left else {
+            panic!("expected and below or: {:?}", left);
+        };
+        assert!(matches!(*left, Node::Not(_)));
+        assert!(matches!(*right, Node::Name(_)));
+    }
+
+    #[test]
+    fn parentheses_override_precedence() {
+        let node = parse("name:a and (name:b or name:c)");
+        let Node::And(_, right) = node else {
+            panic!("expected and at the top: {:?}", node);
+        };
+        assert!(matches!(*right, Node::Or(_, _)));
+    }
+
+    #[test]
+    fn keywords_are_case_insensitive_whole_words() {
+        assert!(matches!(parse("name:a AND name:b"), Node::And(_, _)));
+        // "android" is a value, not "and" followed by "roid"
+        assert!(matches!(parse("name:android"), Node::Name(_)));
+        assert!(error("name:a andx name:b").contains("unexpected input"));
+    }
+
+    #[test]
+    fn unbalanced_parentheses_are_errors() {
+        assert!(error("(name:a or name:b").contains("expected ')'"));
+        assert!(error("name:a)").contains("unexpected input"));
+        assert!(error("()").contains("expected a filter term"));
+    }
+
+    #[test]
+    fn quoted_values_keep_spaces() {
+        let Node::Name(pattern) = parse("name:\"my file.txt\"") else {
+            panic!("expected a name term");
+        };
+        assert!(pattern.matches("my file.txt"));
+        assert!(error("name:\"my file.txt").contains("unterminated quote"));
+        assert!(error("name:").contains("expected a value"));
+    }
+
+    #[test]
+    fn field_errors() {
+        assert!(error("owner:root").contains("unknown field 'owner'"));
+        assert!(error("name a").contains("expected ':' after 'name'"));
+        assert!(error("size 10").contains("expected a comparison"));
+        assert!(error("name:[").contains("invalid pattern '['"));
+        let mtime = error("mtime < 3x");
+        assert!(mtime.contains("unknown duration unit 'x'") && mtime.contains("YYYY-MM-DD"));
+    }
+
+    #[test]
+    fn size_units_are_binary_multiples() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("512B"), Ok(512));
+        assert_eq!(parse_size("10k"), Ok(10 << 10));
+        assert_eq!(parse_size("10KB"), Ok(10 << 10));
+        assert_eq!(parse_size("1.5GB"), Ok(3 << 29));
+        assert_eq!(parse_size("2tb"), Ok(2 << 40));
+        assert!(parse_size("10XB").unwrap_err().contains("unknown size unit 'XB'"));
+        assert!(parse_size("KB").unwrap_err().contains("invalid size 'KB'"));
+        assert!(matches!(parse("size >= 1MB"), Node::Size(Comparison::GreaterOrEqual, 1048576)));
+    }
+
+    #[test]
+    fn matches_files() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        let path = root.join("logs").join("app.LOG");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, vec![b'x'; 2048]).unwrap();
+        let metadata = fs::metadata(&path).unwrap();
+        let matches = |expression: &str| {
+            Filter::parse(expression)
+                .unwrap()
+                .matches(root, &path, &metadata)
+        };
+
+        let results = [
+            matches("ext:log and size > 1KB"),
+            matches("ext:.log and size > 2KB"),
+            matches("path:logs/* and not name:*.txt"),
+            matches("name:*.txt or (size = 2KB and mtime > 1d)"),
+            matches("mtime < 1d"),
+            matches("mtime > 2000-01-01"),
+        ];
+
+        assert_eq!(results, [true, false, true, true, false, true]);
+    }
+
+    #[test]
+    fn mtime_compares_timestamps_and_whole_days() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("old.txt");
+        let file = fs::File::create(&path).unwrap();
+        // 2024-01-01 12:00 UTC
+        let noon = SystemTime::UNIX_EPOCH + Duration::from_secs(1_704_110_400);
+        file.set_modified(noon).unwrap();
+        let metadata = fs::metadata(&path).unwrap();
+        let matches = |expression: &str| {
+            Filter::parse(expression)
+                .unwrap()
+                .matches(temp.path(), &path, &metadata)
+        };
+
+        assert!(matches("mtime < 30d") && !matches("mtime >= 30d"));
+        assert!(matches("mtime = 2024-01-01") && !matches("mtime != 2024-01-01"));
+        assert!(matches("mtime <= 2024-01-01") && !matches("mtime < 2024-01-01"));
+        assert!(matches("mtime >= 2024-01-01") && !matches("mtime > 2024-01-01"));
+        assert!(matches("mtime > 2023-12-31") && matches("mtime < 2024-01-02"));
+        assert!(error("mtime = 1d").contains("an age only compares with"));
+        assert!(error("mtime != 1d").contains("an age only compares with"));
+    }
+}
@@ -0,0 +1,61 @@
+//! File system analysis and search, as used by the `fs-tool` CLI.
+//!
+//! Configure a scan with [`model::AnalyzeOptions::builder`] or
+//! [`model::SearchOptions::builder`] and run it with [`directory_analyzer`] or [`file_finder`]; results come back
+//! as [`model::DirectorySummary`] and [`model::SearchResult`].
+//!
+//! ```no_run
+//! use fs_tool::model::{AnalyzeOptions, SizeMetric};
+//!
+//! let options = AnalyzeOptions::builder("/srv/data".into())
+//!     .with_top_n(10)
+//!     .with_ignore_patterns(vec!["*.tmp".to_string()])
+//!     .with_usage(SizeMetric::Disk)
+//!     .build()?;
+//! let summary = fs_tool::directory_analyzer(&options)?;
+//! for folder in summary.largest_folders() {
+//!     println!("{} {}", folder.path().display(), folder.disk_usage());
+//! }
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! # Stability
+//!
+//! The crate follows semantic versioning. Everything re-exported at the crate
+//! root and everything in [`model`] is public API: while the version is 0.x,
+//! breaking changes to it only come with a minor version bump (0.1 to 0.2),
+//! and patch releases only add to it. Result types keep their fields private
+//! and can only be read, as only the crate constructs and fills them, and
+//! options are only constructed through their builders, so new fields and
+//! settings aren't breaking changes. The errors and the enums in [`model`]
+//! are `#[non_exhaustive]`, so matches on them need a wildcard arm.
+//!
+//! Not covered: the JSON shape of `Serialize` output beyond what the snapshot
+//! format version promises, and the `utils` module, which holds helpers for
+//! the bundled CLI and may change in any release.
+
+mod analyzer;
+mod cancel;
+mod duplicates;
+mod error;
+mod export;
+mod filter;
+pub mod model;
+mod owners;
+mod progress;
+mod report;
+mod search;
+mod snapshot;
+mod tree;
+#[doc(hidden)]
+pub mod utils;
+mod walker;
+
+pub use analyzer::directory_analyzer;
+pub use error::{AnalysisError, OptionsError};
+pub use export::{export_csv, export_sqlite};
+pub use filter::Filter;
+pub use report::save_html_report;
+pub use search::{file_finder, stream_files};
+pub use snapshot::{load_snapshot, save_snapshot, snapshot_diff};
+pub use tree::{NodeKind, SizeNode, SizeTree, SortKey};
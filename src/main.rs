@@ -1,15 +1,20 @@
-use clap::{Parser, Subcommand};
-use glob::Pattern;
+use clap::{ArgGroup, Parser, Subcommand};
 use std::path::PathBuf;
+use std::sync::Arc;
 
-mod analyzer;
 mod display;
-mod error;
-mod model;
-mod search;
-mod utils;
+mod status;
+mod tui;
 
-use crate::model::{AnalyzeOptions, SearchOptions};
+use fs_tool::AnalysisError;
+use fs_tool::model::{
+    AnalyzeOptions, CancelToken, ContentMatchOptions, DiffOptions, FileType, HashAlgorithm,
+    SearchOptions, SizeMetric, TimeField, TypeSelector,
+};
+use fs_tool::utils;
+
+use crate::display::{AnalyzeOutput, OutputFormat};
+use crate::status::StatusLine;
 
 #[derive(Parser)]
 #[command(
@@ -27,9 +32,11 @@ struct Cli {
 enum Commands {
     Analyze(AnalyzeCommand),
     Search(SearchCommand),
+    Diff(DiffCommand),
 }
 
 #[derive(clap::Args)]
+#[command(group(ArgGroup::new("export").multiple(true)))]
 struct AnalyzeCommand {
     #[arg(default_value = ".")]
     path: PathBuf,
@@ -45,6 +52,57 @@ struct AnalyzeCommand {
     duplicates: bool,
     #[arg(short = 'i', long, value_delimiter = ',')]
     ignore: Option<Vec<String>>,
+    #[arg(short = 'f', long, default_value = "text")]
+    format: OutputFormat,
+    #[arg(short = 'j', long, default_value_t = 0)]
+    threads: usize,
+    #[arg(long, default_value = "sha256")]
+    hash_algo: HashAlgorithm,
+    #[arg(long)]
+    save_snapshot: Option<PathBuf>,
+    #[arg(long, requires = "save_snapshot")]
+    snapshot_hashes: bool,
+    #[arg(long, conflicts_with = "format")]
+    interactive: bool,
+    #[arg(long, default_value = "apparent")]
+    usage: SizeMetric,
+    #[arg(short = 'x', long)]
+    one_file_system: bool,
+    #[arg(long)]
+    respect_gitignore: bool,
+    #[arg(long)]
+    by_type: bool,
+    #[arg(long, value_name = "TYPE")]
+    folders_by_type: Option<TypeSelector>,
+    #[arg(long)]
+    age_histogram: bool,
+    #[arg(long, default_value = "mtime", requires = "age_histogram")]
+    age_field: TimeField,
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_value = "1d,7d,30d,1y",
+        requires = "age_histogram"
+    )]
+    age_buckets: Vec<String>,
+    #[arg(long)]
+    by_owner: bool,
+    #[arg(long, conflicts_with_all = ["interactive", "format"])]
+    tree: bool,
+    #[arg(long, default_value_t = 3, requires = "tree")]
+    tree_depth: usize,
+    #[arg(long, value_name = "FILE")]
+    html: Option<PathBuf>,
+    #[arg(long, value_name = "FILE", group = "export")]
+    export_csv: Option<PathBuf>,
+    #[arg(long, value_name = "FILE", group = "export")]
+    export_sqlite: Option<PathBuf>,
+    #[arg(long, requires = "export")]
+    export_hashes: bool,
+    #[arg(long)]
+    no_progress: bool,
+    #[arg(long, value_name = "DURATION")]
+    timeout: Option<String>,
 }
 
 #[derive(clap::Args)]
@@ -55,6 +113,24 @@ struct SearchCommand {
     name_pattern: Vec<String>,
     #[arg(short = 'c', long)]
     content_pattern: Option<String>,
+    #[arg(short = 'E', long, requires = "content_pattern")]
+    regex: bool,
+    #[arg(short = 'F', long, requires = "content_pattern", conflicts_with = "regex")]
+    fixed_strings: bool,
+    #[arg(short = 'i', long, requires = "content_pattern")]
+    ignore_case: bool,
+    #[arg(short = 'w', long, requires = "content_pattern")]
+    word: bool,
+    #[arg(short = 'm', long, requires = "content_pattern")]
+    max_count: Option<usize>,
+    #[arg(short = 'A', long, requires = "content_pattern")]
+    after_context: Option<usize>,
+    #[arg(short = 'B', long, requires = "content_pattern")]
+    before_context: Option<usize>,
+    #[arg(short = 'C', long, requires = "content_pattern")]
+    context: Option<usize>,
+    #[arg(long, requires = "content_pattern")]
+    binary: bool,
     #[arg(short = 'a', long)]
     modified_after: Option<String>,
     #[arg(short = 'b', long)]
@@ -63,6 +139,42 @@ struct SearchCommand {
     min_size: Option<u64>,
     #[arg(long, alias = "max")]
     max_size: Option<u64>,
+    #[arg(short = 'x', long)]
+    one_file_system: bool,
+    #[arg(long)]
+    respect_gitignore: bool,
+    #[arg(short = 't', long = "type", value_delimiter = ',')]
+    file_type: Vec<FileType>,
+    #[arg(
+        long = "where",
+        value_name = "EXPR",
+        help = "Filter expression, e.g. 'ext:log and size>10MB'. mtime compares timestamps: \
+                mtime<30d is modified before 30 days ago, mtime>=2024-01-01 on or after that day"
+    )]
+    filter: Option<String>,
+    #[arg(long)]
+    sort: bool,
+    #[arg(short = 'f', long, default_value = "text")]
+    format: OutputFormat,
+    #[arg(short = 'j', long, default_value_t = 0)]
+    threads: usize,
+    #[arg(long)]
+    no_progress: bool,
+}
+
+#[derive(clap::Args)]
+struct DiffCommand {
+    old: PathBuf,
+    #[arg(default_value = ".")]
+    new: PathBuf,
+    #[arg(short = 'n', long, default_value_t = 10)]
+    top_n: usize,
+    #[arg(short = 'i', long, value_delimiter = ',')]
+    ignore: Option<Vec<String>>,
+    #[arg(short = 'f', long, default_value = "text")]
+    format: OutputFormat,
+    #[arg(short = 'j', long, default_value_t = 0)]
+    threads: usize,
 }
 
 fn main() {
@@ -71,18 +183,36 @@ fn main() {
     match cli.command {
         Some(Commands::Analyze(analyze_cmd)) => handle_analyze(analyze_cmd),
         Some(Commands::Search(search_cmd)) => handle_search(search_cmd),
+        Some(Commands::Diff(diff_cmd)) => handle_diff(diff_cmd),
         None => handle_legacy_analyze(),
     }
 }
 
 fn handle_analyze(cmd: AnalyzeCommand) {
-    match convert_analyze_command(cmd) {
-        Ok(options) => {
-            if let Err(e) = display::display_directory_analyzer(options) {
+    let cancel = cancel_on_ctrl_c();
+    let status = StatusLine::stderr("Scanning", !cmd.no_progress);
+    let output = AnalyzeOutput::new(cmd.format)
+        .with_status(status.clone())
+        .with_save_snapshot(cmd.save_snapshot.clone())
+        .with_html(cmd.html.clone())
+        .with_export_csv(cmd.export_csv.clone())
+        .with_export_sqlite(cmd.export_sqlite.clone())
+        .with_interactive(cmd.interactive);
+    let output = if cmd.tree {
+        output.with_tree(cmd.tree_depth)
+    } else {
+        output
+    };
+    match convert_analyze_command(cmd, status.as_ref(), cancel) {
+        Ok(options) => match display::display_directory_analyzer(options, &output) {
+            Ok(None) => {}
+            // Partial results were printed
+            Ok(Some(reason)) => std::process::exit(reason.exit_code()),
+            Err(e) => {
                 eprintln!("Error: {}", e);
                 std::process::exit(1);
             }
-        }
+        },
         Err(e) => {
             eprintln!("Error: {}", e);
             std::process::exit(1);
@@ -90,10 +220,30 @@ fn handle_analyze(cmd: AnalyzeCommand) {
     }
 }
 
+// The first Ctrl-C stops the scan and keeps what it found, a second one exits
+fn cancel_on_ctrl_c() -> CancelToken {
+    let cancel = CancelToken::new();
+    let handler_cancel = cancel.clone();
+    let handler = ctrlc::set_handler(move || {
+        if handler_cancel.is_cancelled() {
+            std::process::exit(130);
+        }
+        handler_cancel.cancel();
+    });
+    if let Err(e) = handler {
+        eprintln!("Warning: Cannot handle Ctrl-C: {}", e);
+    }
+    cancel
+}
+
 fn handle_search(cmd: SearchCommand) {
-    match convert_search_command(cmd) {
+    let format = cmd.format;
+    let sort = cmd.sort;
+    let status = StatusLine::stderr("Searching", !cmd.no_progress);
+    match convert_search_command(cmd, status.as_ref()) {
         Ok(options) => {
-            if let Err(e) = display::display_search_result(options) {
+            if let Err(e) = display::display_search_result(options, format, sort, status.as_deref())
+            {
                 eprintln!("Search error: {}", e);
                 std::process::exit(1);
             }
@@ -105,6 +255,30 @@ fn handle_search(cmd: SearchCommand) {
     }
 }
 
+fn handle_diff(cmd: DiffCommand) {
+    let format = cmd.format;
+    let options = match DiffOptions::builder(cmd.old, cmd.new)
+        .with_top_n(cmd.top_n)
+        .with_ignore_patterns(cmd.ignore.unwrap_or_default())
+        .with_threads(cmd.threads)
+        .with_cancel_token(cancel_on_ctrl_c())
+        .build()
+    {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if let Err(e) = display::display_snapshot_diff(options, format) {
+        eprintln!("Diff error: {}", e);
+        match e.downcast_ref::<AnalysisError>() {
+            Some(AnalysisError::Interrupted(reason)) => std::process::exit(reason.exit_code()),
+            _ => std::process::exit(1),
+        }
+    }
+}
+
 fn handle_legacy_analyze() {
     let cmd = AnalyzeCommand {
         path: PathBuf::from("."),
@@ -114,38 +288,130 @@ fn handle_legacy_analyze() {
         min_size: None,
         duplicates: false,
         ignore: None,
+        format: OutputFormat::Text,
+        threads: 0,
+        hash_algo: HashAlgorithm::Sha256,
+        save_snapshot: None,
+        snapshot_hashes: false,
+        interactive: false,
+        usage: SizeMetric::Apparent,
+        one_file_system: false,
+        respect_gitignore: false,
+        by_type: false,
+        folders_by_type: None,
+        age_histogram: false,
+        age_field: TimeField::Mtime,
+        age_buckets: Vec::new(),
+        by_owner: false,
+        tree: false,
+        tree_depth: 3,
+        html: None,
+        export_csv: None,
+        export_sqlite: None,
+        export_hashes: false,
+        no_progress: false,
+        timeout: None,
     };
     handle_analyze(cmd)
 }
 
-fn convert_analyze_command(cmd: AnalyzeCommand) -> Result<AnalyzeOptions, String> {
-    AnalyzeOptions::new(
-        cmd.path,
-        cmd.max_depth,
-        cmd.top_n,
-        cmd.follow_symlinks,
-        cmd.min_size,
-        cmd.duplicates,
-        cmd.ignore.unwrap_or_default(),
-    )
-}
-fn convert_search_command(cmd: SearchCommand) -> Result<SearchOptions, String> {
-    let name_pattern = cmd
-        .name_pattern
-        .into_iter()
-        .map(|s| Pattern::new(&s).map_err(|e| format!("Invalid name pattern '{}': {}", s, e)))
+fn convert_analyze_command(
+    cmd: AnalyzeCommand,
+    status: Option<&Arc<StatusLine>>,
+    cancel: CancelToken,
+) -> Result<AnalyzeOptions, String> {
+    let age_buckets = cmd
+        .age_buckets
+        .iter()
+        .map(|bucket| {
+            utils::parse_age(bucket).map_err(|e| format!("Invalid age bucket: {}", e))
+        })
         .collect::<Result<Vec<_>, _>>()?;
+    let timeout = cmd
+        .timeout
+        .as_deref()
+        .map(|timeout| {
+            utils::parse_age(timeout).map_err(|e| format!("Invalid timeout: {}", e))
+        })
+        .transpose()?;
 
+    let builder = AnalyzeOptions::builder(cmd.path)
+        .with_max_depth(cmd.max_depth)
+        .with_top_n(cmd.top_n)
+        .with_follow_symlinks(cmd.follow_symlinks)
+        .with_min_size(cmd.min_size)
+        .with_duplicates(cmd.duplicates)
+        .with_ignore_patterns(cmd.ignore.unwrap_or_default())
+        .with_threads(cmd.threads)
+        .with_hash_algorithm(cmd.hash_algo)
+        .with_usage(cmd.usage)
+        .with_one_file_system(cmd.one_file_system)
+        .with_respect_gitignore(cmd.respect_gitignore)
+        .with_by_type(cmd.by_type)
+        .with_folders_by_type(cmd.folders_by_type)
+        .with_by_owner(cmd.by_owner)
+        .with_timeout(timeout)
+        .with_cancel_token(cancel);
+    let builder = if cmd.age_histogram {
+        builder.with_age_histogram(cmd.age_field, age_buckets)
+    } else {
+        builder
+    };
+    let builder = if cmd.save_snapshot.is_some() {
+        builder.with_snapshot(cmd.snapshot_hashes)
+    } else {
+        builder
+    };
+    let builder = if cmd.export_csv.is_some() || cmd.export_sqlite.is_some() {
+        builder.with_inventory(cmd.export_hashes)
+    } else {
+        builder
+    };
+    let builder = if cmd.interactive || cmd.tree || cmd.html.is_some() {
+        builder.with_size_tree()
+    } else {
+        builder
+    };
+    let builder = match status {
+        Some(status) => builder.with_progress(status.callback()),
+        None => builder,
+    };
+    builder.build().map_err(|e| e.to_string())
+}
+fn convert_search_command(
+    cmd: SearchCommand,
+    status: Option<&Arc<StatusLine>>,
+) -> Result<SearchOptions, String> {
     let modified_after = utils::parse_date(cmd.modified_after, "modified_after")?;
     let modified_before = utils::parse_date(cmd.modified_before, "modified_before")?;
 
-    SearchOptions::new(
-        cmd.path,
-        name_pattern,
-        cmd.content_pattern,
-        modified_after,
-        modified_before,
-        cmd.min_size,
-        cmd.max_size,
-    )
+    let builder = SearchOptions::builder(cmd.path)
+        .with_name_patterns(cmd.name_pattern)
+        .with_content_pattern(cmd.content_pattern)
+        .with_content_match_options(
+            ContentMatchOptions::default()
+                .with_regex(cmd.regex && !cmd.fixed_strings)
+                .with_case_insensitive(cmd.ignore_case)
+                .with_whole_word(cmd.word)
+                .with_max_count(cmd.max_count)
+                .with_context(
+                    cmd.before_context.or(cmd.context).unwrap_or_default(),
+                    cmd.after_context.or(cmd.context).unwrap_or_default(),
+                )
+                .with_binary(cmd.binary),
+        )
+        .with_modified_after(modified_after)
+        .with_modified_before(modified_before)
+        .with_min_size(cmd.min_size)
+        .with_max_size(cmd.max_size)
+        .with_threads(cmd.threads)
+        .with_filter(cmd.filter)
+        .with_file_types(cmd.file_type)
+        .with_one_file_system(cmd.one_file_system)
+        .with_respect_gitignore(cmd.respect_gitignore);
+    let builder = match status {
+        Some(status) => builder.with_progress(status.callback()),
+        None => builder,
+    };
+    builder.build().map_err(|e| e.to_string())
 }
@@ -1,46 +1,137 @@
 use clap::error::Result;
 use glob::Pattern;
+use regex::{bytes, Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
 
+use std::fs::Metadata;
+use std::ops::{AddAssign, Range};
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
-use std::time::SystemTime;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 
+use crate::error::OptionsError;
+use crate::filter::Filter;
+use crate::tree::SizeTree;
 use crate::utils;
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Eq, PartialEq, Serialize)]
 pub struct FileInfoDirectory {
+    #[serde(serialize_with = "utils::serialize_path")]
     path: PathBuf,
     size: u64,
+    disk_usage: u64,
+    // Value the heap orders by, the size unless set otherwise
+    #[serde(skip)]
+    rank: u64,
 }
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Eq, PartialEq, Serialize)]
 pub struct FolderInfo {
+    #[serde(serialize_with = "utils::serialize_path")]
     path: PathBuf,
     size: u64,
+    disk_usage: u64,
+    // Bytes of the type folders are ranked by, with --folders-by-type
+    #[serde(skip_serializing_if = "Option::is_none")]
+    type_size: Option<u64>,
+    #[serde(skip)]
+    rank: u64,
 }
 
-#[derive(Debug)]
+/// Apparent size (file length) and allocated size (blocks on disk) of a file
+/// or a whole folder.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Usage {
+    apparent: u64,
+    disk: u64,
+}
+
+#[derive(Debug, Serialize)]
 pub struct DuplicateGroup {
+    // One path per inode: true copies of the same content
+    #[serde(serialize_with = "utils::serialize_paths")]
     files: Vec<PathBuf>,
     size: u64,
     hash: String,
+    // Further names of the files above, which take no extra space
+    hard_links: Vec<HardLink>,
 }
-#[derive(Debug)]
+
+/// An extra name for `target`'s inode.
+#[derive(Debug, Clone, Serialize)]
+pub struct HardLink {
+    #[serde(serialize_with = "utils::serialize_path")]
+    path: PathBuf,
+    #[serde(serialize_with = "utils::serialize_path")]
+    target: PathBuf,
+}
+
+/// A directory where another filesystem is mounted, met during a walk.
+#[derive(Debug, Clone, Serialize)]
+pub struct MountPoint {
+    #[serde(serialize_with = "utils::serialize_path")]
+    path: PathBuf,
+    // Whether the walk descended into it (`false` with --one-file-system)
+    included: bool,
+}
+
+/// Inodes reached through more than one path during a scan.
+#[derive(Debug, Default, Serialize)]
+pub struct HardLinkSummary {
+    inodes: u64,
+    extra_links: u64,
+    // Bytes the extra links would take as separate copies
+    bytes_saved: u64,
+}
+#[derive(Debug, Serialize)]
 pub struct DirectorySummary {
     total_size: u64,
+    disk_usage: u64,
     file_count: u64,
     folder_count: u64,
     symlink_count: u64,
+    #[serde(rename = "duration_ms", serialize_with = "utils::serialize_duration_ms")]
     duration: std::time::Duration,
+    // Why the scan stopped early; the counts only cover what was seen
+    #[serde(skip_serializing_if = "Option::is_none")]
+    incomplete: Option<StopReason>,
     largest_files: Vec<FileInfoDirectory>,
     largest_folders: Vec<FolderInfo>,
+    sparse_files: Vec<FileInfoDirectory>,
+    hard_links: HardLinkSummary,
+    mount_points: Vec<MountPoint>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    usage_by_type: Option<TypeBreakdown>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    age_histogram: Option<AgeHistogram>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    usage_by_owner: Option<OwnerBreakdown>,
     duplicates: Option<Vec<DuplicateGroup>>,
+    #[serde(skip)]
+    snapshot: Option<Snapshot>,
+    #[serde(skip)]
+    size_tree: Option<SizeTree>,
+    #[serde(skip)]
+    inventory: Option<Vec<InventoryEntry>>,
 }
 
 // FileInfo implementations
 impl FileInfoDirectory {
-    pub fn new(path: PathBuf, size: u64) -> Self {
-        FileInfoDirectory { path, size }
+    pub(crate) fn new(path: PathBuf, usage: Usage) -> Self {
+        FileInfoDirectory {
+            path,
+            size: usage.apparent,
+            disk_usage: usage.disk,
+            rank: usage.apparent,
+        }
+    }
+
+    /// Orders the file by `rank` instead of its apparent size.
+    pub(crate) fn with_rank(mut self, rank: u64) -> Self {
+        self.rank = rank;
+        self
     }
 
     pub fn path(&self) -> &PathBuf {
@@ -50,12 +141,38 @@ impl FileInfoDirectory {
     pub fn size(&self) -> &u64 {
         &self.size
     }
+
+    pub fn disk_usage(&self) -> &u64 {
+        &self.disk_usage
+    }
 }
 
 // FolderInfo implementations
 impl FolderInfo {
-    pub fn new(path: PathBuf, size: u64) -> Self {
-        FolderInfo { path, size }
+    pub(crate) fn new(path: PathBuf, usage: Usage) -> Self {
+        FolderInfo {
+            path,
+            size: usage.apparent,
+            disk_usage: usage.disk,
+            type_size: None,
+            rank: usage.apparent,
+        }
+    }
+
+    pub(crate) fn with_rank(mut self, rank: u64) -> Self {
+        self.rank = rank;
+        self
+    }
+
+    /// Ranks the folder by the bytes of one file type it holds.
+    pub(crate) fn with_type_size(mut self, type_size: u64) -> Self {
+        self.type_size = Some(type_size);
+        self.rank = type_size;
+        self
+    }
+
+    pub fn type_size(&self) -> Option<u64> {
+        self.type_size
     }
 
     pub fn path(&self) -> &PathBuf {
@@ -65,11 +182,60 @@ impl FolderInfo {
     pub fn size(&self) -> &u64 {
         &self.size
     }
+
+    pub fn disk_usage(&self) -> &u64 {
+        &self.disk_usage
+    }
+}
+
+impl Usage {
+    pub(crate) fn new(apparent: u64, disk: u64) -> Self {
+        Usage { apparent, disk }
+    }
+
+    pub(crate) fn from_metadata(metadata: &Metadata) -> Self {
+        Usage::new(metadata.len(), utils::disk_usage(metadata))
+    }
+
+    pub fn apparent(&self) -> u64 {
+        self.apparent
+    }
+
+    pub fn disk(&self) -> u64 {
+        self.disk
+    }
+
+    pub fn get(&self, metric: SizeMetric) -> u64 {
+        match metric {
+            SizeMetric::Apparent => self.apparent,
+            SizeMetric::Disk => self.disk,
+        }
+    }
+}
+
+impl AddAssign for Usage {
+    fn add_assign(&mut self, other: Self) {
+        self.apparent += other.apparent;
+        self.disk += other.disk;
+    }
 }
 
 impl DuplicateGroup {
-    pub fn new(files: Vec<PathBuf>, size: u64, hash: String) -> Self {
-        DuplicateGroup { files, size, hash }
+    pub(crate) fn new(files: Vec<PathBuf>, size: u64, hash: String) -> Self {
+        DuplicateGroup {
+            files,
+            size,
+            hash,
+            hard_links: Vec::new(),
+        }
+    }
+
+    pub(crate) fn set_hard_links(&mut self, hard_links: Vec<HardLink>) {
+        self.hard_links = hard_links;
+    }
+
+    pub fn hard_links(&self) -> &Vec<HardLink> {
+        &self.hard_links
     }
 
     pub fn files(&self) -> &Vec<PathBuf> {
@@ -85,8 +251,51 @@ impl DuplicateGroup {
     }
 }
 
+impl HardLink {
+    pub(crate) fn new(path: PathBuf, target: PathBuf) -> Self {
+        HardLink { path, target }
+    }
+
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    pub fn target(&self) -> &PathBuf {
+        &self.target
+    }
+}
+
+impl MountPoint {
+    pub(crate) fn new(path: PathBuf, included: bool) -> Self {
+        MountPoint { path, included }
+    }
+
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    pub fn included(&self) -> bool {
+        self.included
+    }
+}
+
+impl HardLinkSummary {
+    pub fn inodes(&self) -> &u64 {
+        &self.inodes
+    }
+
+    pub fn extra_links(&self) -> &u64 {
+        &self.extra_links
+    }
+
+    pub fn bytes_saved(&self) -> &u64 {
+        &self.bytes_saved
+    }
+}
+
 impl DirectorySummary {
-    pub fn new(
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
         total_size: u64,
         file_count: u64,
         folder_count: u64,
@@ -98,13 +307,24 @@ impl DirectorySummary {
     ) -> Self {
         DirectorySummary {
             total_size,
+            disk_usage: 0,
             file_count,
             folder_count,
             symlink_count,
             duration,
+            incomplete: None,
             largest_files,
             largest_folders,
+            sparse_files: Vec::new(),
+            hard_links: HardLinkSummary::default(),
+            mount_points: Vec::new(),
+            usage_by_type: None,
+            age_histogram: None,
+            usage_by_owner: None,
             duplicates,
+            snapshot: None,
+            size_tree: None,
+            inventory: None,
         }
     }
 
@@ -112,6 +332,10 @@ impl DirectorySummary {
         &self.total_size
     }
 
+    pub fn disk_usage(&self) -> &u64 {
+        &self.disk_usage
+    }
+
     pub fn file_count(&self) -> &u64 {
         &self.file_count
     }
@@ -128,6 +352,11 @@ impl DirectorySummary {
         self.duration
     }
 
+    /// Why the scan stopped before covering the whole tree, if it did.
+    pub fn incomplete(&self) -> Option<StopReason> {
+        self.incomplete
+    }
+
     pub fn largest_files(&self) -> &Vec<FileInfoDirectory> {
         &self.largest_files
     }
@@ -136,39 +365,127 @@ impl DirectorySummary {
         &self.largest_folders
     }
 
-    pub fn duplicates_mut(&mut self) -> &mut Option<Vec<DuplicateGroup>> {
+    pub fn sparse_files(&self) -> &Vec<FileInfoDirectory> {
+        &self.sparse_files
+    }
+
+    pub fn hard_links(&self) -> &HardLinkSummary {
+        &self.hard_links
+    }
+
+    pub fn mount_points(&self) -> &Vec<MountPoint> {
+        &self.mount_points
+    }
+
+    pub(crate) fn set_mount_points(&mut self, mount_points: Vec<MountPoint>) {
+        self.mount_points = mount_points;
+    }
+
+    pub fn usage_by_type(&self) -> &Option<TypeBreakdown> {
+        &self.usage_by_type
+    }
+
+    pub(crate) fn set_usage_by_type(&mut self, usage_by_type: TypeBreakdown) {
+        self.usage_by_type = Some(usage_by_type);
+    }
+
+    pub fn age_histogram(&self) -> &Option<AgeHistogram> {
+        &self.age_histogram
+    }
+
+    pub(crate) fn set_age_histogram(&mut self, age_histogram: AgeHistogram) {
+        self.age_histogram = Some(age_histogram);
+    }
+
+    pub fn usage_by_owner(&self) -> &Option<OwnerBreakdown> {
+        &self.usage_by_owner
+    }
+
+    pub(crate) fn set_usage_by_owner(&mut self, usage_by_owner: OwnerBreakdown) {
+        self.usage_by_owner = Some(usage_by_owner);
+    }
+
+    /// Records an inode of `size` bytes found under `extra_links` more names.
+    pub(crate) fn add_hard_link(&mut self, size: u64, extra_links: u64) {
+        self.hard_links.inodes += 1;
+        self.hard_links.extra_links += extra_links;
+        self.hard_links.bytes_saved += size * extra_links;
+    }
+
+    pub(crate) fn duplicates_mut(&mut self) -> &mut Option<Vec<DuplicateGroup>> {
         &mut self.duplicates
     }
     pub fn duplicates(&self) -> &Option<Vec<DuplicateGroup>> {
         &self.duplicates
     }
-    pub fn add_to_total_size(&mut self, size: u64) {
-        self.total_size += size;
+    pub(crate) fn add_to_total_size(&mut self, usage: Usage) {
+        self.total_size += usage.apparent;
+        self.disk_usage += usage.disk;
     }
 
-    pub fn increment_file_count(&mut self) {
+    pub(crate) fn increment_file_count(&mut self) {
         self.file_count += 1;
     }
 
-    pub fn increment_folder_count(&mut self) {
+    pub(crate) fn increment_folder_count(&mut self) {
         self.folder_count += 1;
     }
 
-    pub fn increment_symlink_count(&mut self) {
+    pub(crate) fn increment_symlink_count(&mut self) {
         self.symlink_count += 1;
     }
 
-    pub fn set_duration(&mut self, duration: std::time::Duration) {
+    pub(crate) fn set_duration(&mut self, duration: std::time::Duration) {
         self.duration = duration;
     }
 
-    pub fn set_largest_files(&mut self, files: Vec<FileInfoDirectory>) {
+    pub(crate) fn set_incomplete(&mut self, reason: Option<StopReason>) {
+        self.incomplete = reason;
+    }
+
+    pub(crate) fn set_largest_files(&mut self, files: Vec<FileInfoDirectory>) {
         self.largest_files = files;
     }
 
-    pub fn set_largest_folders(&mut self, folders: Vec<FolderInfo>) {
+    pub(crate) fn set_largest_folders(&mut self, folders: Vec<FolderInfo>) {
         self.largest_folders = folders;
     }
+
+    pub(crate) fn set_sparse_files(&mut self, files: Vec<FileInfoDirectory>) {
+        self.sparse_files = files;
+    }
+
+    pub fn snapshot(&self) -> &Option<Snapshot> {
+        &self.snapshot
+    }
+
+    pub(crate) fn set_snapshot(&mut self, snapshot: Snapshot) {
+        self.snapshot = Some(snapshot);
+    }
+
+    pub fn take_snapshot(&mut self) -> Option<Snapshot> {
+        self.snapshot.take()
+    }
+
+    pub fn inventory(&self) -> &Option<Vec<InventoryEntry>> {
+        &self.inventory
+    }
+
+    pub(crate) fn set_inventory(&mut self, inventory: Vec<InventoryEntry>) {
+        self.inventory = Some(inventory);
+    }
+
+    pub(crate) fn set_size_tree(&mut self, size_tree: SizeTree) {
+        self.size_tree = Some(size_tree);
+    }
+
+    pub fn size_tree(&self) -> &Option<SizeTree> {
+        &self.size_tree
+    }
+
+    pub fn take_size_tree(&mut self) -> Option<SizeTree> {
+        self.size_tree.take()
+    }
 }
 
 pub struct AnalyzeOptions {
@@ -179,76 +496,64 @@ pub struct AnalyzeOptions {
     min_size: Option<u64>,
     detect_duplicates: bool,
     ignore_patterns: Vec<Pattern>,
+    threads: usize,
+    hash_algorithm: HashAlgorithm,
+    snapshot: bool,
+    snapshot_hashes: bool,
+    inventory: bool,
+    inventory_hashes: bool,
+    size_tree: bool,
+    usage: SizeMetric,
+    one_file_system: bool,
+    respect_gitignore: bool,
+    by_type: bool,
+    folders_by_type: Option<TypeSelector>,
+    age_field: TimeField,
+    // Bucket boundaries, ascending; `None` leaves the histogram out
+    age_buckets: Option<Vec<Duration>>,
+    by_owner: bool,
+    progress: Option<ProgressCallback>,
+    cancel_token: Option<CancelToken>,
+    timeout: Option<Duration>,
 }
 
 impl AnalyzeOptions {
-    pub fn new(
-        path: PathBuf,
-        max_depth: Option<usize>,
-        top_n: usize,
-        follow_symlinks: bool,
-        min_size_string: Option<String>,
-        detect_duplicates: bool,
-        ignore_patterns: Vec<String>,
-    ) -> Result<AnalyzeOptions, String> {
-        // Convert string patterns to compiled glob patterns
-        let ignore_patterns = ignore_patterns
-            .into_iter()
-            .filter_map(|p| Pattern::new(&p).ok())
-            .collect();
-
-        // Convert readable size into size in bytes
-        let min_size: Option<u64> = match min_size_string {
-            Some(input) => {
-                let input = input.trim();
-                if let Ok(bytes) = u64::from_str(input) {
-                    Some(bytes)
-                } else {
-                    let suffixes: [(&str, u64); 4] = [
-                        ("KB", 1024),
-                        ("MB", 1024 * 1024),
-                        ("GB", 1024 * 1024 * 1024),
-                        ("TB", 1024 * 1024 * 1024 * 1024),
-                    ];
-
-                    let mut calculated_size: Option<u64> = None;
-
-                    for (suffix, multiplier) in suffixes.iter() {
-                        if input.ends_with(suffix) {
-                            let number_part = &input[..input.len() - suffix.len()].trim();
-                            if let Ok(number) = number_part.parse::<f64>() {
-                                calculated_size =
-                                    Some((number * *multiplier as f64).round() as u64);
-                                break;
-                            } else {
-                                return Err(format!("Invalid number part: {}", number_part));
-                            }
-                        }
-                    }
-
-                    // If no valid size is calculated, return an error
-                    match calculated_size {
-                        Some(size) => Some(size),
-                        _none => {
-                            return Err("Invalid size format".to_string());
-                        }
-                    }
-                }
-            }
-            _none => None, // No size provided, keep it as None
-        };
-
-        // Return the final AnalyzeOptions object
-        Ok(AnalyzeOptions {
-            path,
-            max_depth,
-            top_n,
-            follow_symlinks,
-            min_size,
-            detect_duplicates,
-            ignore_patterns,
-        })
+    /// Starts building options for analyzing `path`.
+    pub fn builder(path: PathBuf) -> AnalyzeOptionsBuilder {
+        AnalyzeOptionsBuilder {
+            options: AnalyzeOptions {
+                path,
+                max_depth: None,
+                top_n: 5,
+                follow_symlinks: false,
+                min_size: None,
+                detect_duplicates: false,
+                ignore_patterns: Vec::new(),
+                threads: 0,
+                hash_algorithm: HashAlgorithm::Sha256,
+                snapshot: false,
+                snapshot_hashes: false,
+                inventory: false,
+                inventory_hashes: false,
+                size_tree: false,
+                usage: SizeMetric::Apparent,
+                one_file_system: false,
+                respect_gitignore: false,
+                by_type: false,
+                folders_by_type: None,
+                age_field: TimeField::Mtime,
+                age_buckets: None,
+                by_owner: false,
+                progress: None,
+                cancel_token: None,
+                timeout: None,
+            },
+            min_size: None,
+            ignore_patterns: Vec::new(),
+            hash_algorithm: None,
+        }
     }
+
     pub fn path(&self) -> PathBuf {
         self.path.clone()
     }
@@ -273,6 +578,78 @@ impl AnalyzeOptions {
         self.detect_duplicates
     }
 
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    pub fn hash_algorithm(&self) -> HashAlgorithm {
+        self.hash_algorithm
+    }
+
+    pub fn snapshot(&self) -> bool {
+        self.snapshot
+    }
+
+    pub fn snapshot_hashes(&self) -> bool {
+        self.snapshot_hashes
+    }
+
+    pub fn inventory(&self) -> bool {
+        self.inventory
+    }
+
+    pub fn inventory_hashes(&self) -> bool {
+        self.inventory_hashes
+    }
+
+    pub fn size_tree(&self) -> bool {
+        self.size_tree
+    }
+
+    pub fn usage(&self) -> SizeMetric {
+        self.usage
+    }
+
+    pub fn one_file_system(&self) -> bool {
+        self.one_file_system
+    }
+
+    pub fn respect_gitignore(&self) -> bool {
+        self.respect_gitignore
+    }
+
+    pub fn by_type(&self) -> bool {
+        self.by_type
+    }
+
+    pub fn folders_by_type(&self) -> &Option<TypeSelector> {
+        &self.folders_by_type
+    }
+
+    pub fn age_field(&self) -> TimeField {
+        self.age_field
+    }
+
+    pub fn age_buckets(&self) -> &Option<Vec<Duration>> {
+        &self.age_buckets
+    }
+
+    pub fn by_owner(&self) -> bool {
+        self.by_owner
+    }
+
+    pub fn progress(&self) -> Option<&ProgressCallback> {
+        self.progress.as_ref()
+    }
+
+    pub fn cancel_token(&self) -> Option<&CancelToken> {
+        self.cancel_token.as_ref()
+    }
+
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
     pub fn should_ignore(&self, path: &Path) -> bool {
         if let Some(path_str) = path.to_str() {
             self.ignore_patterns
@@ -284,10 +661,221 @@ impl AnalyzeOptions {
     }
 }
 
+/// Collects settings for [`AnalyzeOptions`]; sizes and patterns are
+/// validated once, in [`build`](Self::build).
+pub struct AnalyzeOptionsBuilder {
+    options: AnalyzeOptions,
+    // Kept as given until build()
+    min_size: Option<String>,
+    ignore_patterns: Vec<String>,
+    hash_algorithm: Option<HashAlgorithm>,
+}
+
+impl AnalyzeOptionsBuilder {
+    pub fn build(self) -> Result<AnalyzeOptions, OptionsError> {
+        let mut options = self.options;
+        if options.top_n == 0 {
+            return Err(OptionsError::ZeroTopN);
+        }
+        let hashes = options.snapshot_hashes || options.inventory_hashes;
+        if hashes && self.hash_algorithm.is_none() {
+            return Err(OptionsError::MissingHashAlgorithm);
+        }
+        if let Some(hash_algorithm) = self.hash_algorithm {
+            options.hash_algorithm = hash_algorithm;
+        }
+        // Patterns compiled elsewhere come first
+        options
+            .ignore_patterns
+            .extend(compile_patterns(self.ignore_patterns)?);
+        options.min_size = self.min_size.as_deref().map(parse_min_size).transpose()?;
+        Ok(options)
+    }
+
+    /// Limits how deep below the root the walk goes.
+    pub fn with_max_depth(mut self, max_depth: Option<usize>) -> Self {
+        self.options.max_depth = max_depth;
+        self
+    }
+
+    /// Sets how many entries the largest-files and -folders lists keep.
+    pub fn with_top_n(mut self, top_n: usize) -> Self {
+        self.options.top_n = top_n;
+        self
+    }
+
+    pub fn with_follow_symlinks(mut self, follow_symlinks: bool) -> Self {
+        self.options.follow_symlinks = follow_symlinks;
+        self
+    }
+
+    /// Skips files smaller than `min_size`: bytes, or a number followed by
+    /// KB, MB, GB or TB.
+    pub fn with_min_size(mut self, min_size: Option<String>) -> Self {
+        self.min_size = min_size;
+        self
+    }
+
+    pub fn with_duplicates(mut self, detect_duplicates: bool) -> Self {
+        self.options.detect_duplicates = detect_duplicates;
+        self
+    }
+
+    /// Skips paths matching any of these globs.
+    pub fn with_ignore_patterns(mut self, ignore_patterns: Vec<String>) -> Self {
+        self.ignore_patterns = ignore_patterns;
+        self
+    }
+
+    pub(crate) fn with_compiled_ignore_patterns(mut self, ignore_patterns: Vec<Pattern>) -> Self {
+        self.options.ignore_patterns = ignore_patterns;
+        self
+    }
+
+    /// Sets the number of walker threads, `0` picks one per core.
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.options.threads = threads;
+        self
+    }
+
+    /// Picks the hash for duplicates (SHA-256 if unset) and for snapshot and
+    /// inventory hashes, which need it set explicitly.
+    pub fn with_hash_algorithm(mut self, hash_algorithm: HashAlgorithm) -> Self {
+        self.hash_algorithm = Some(hash_algorithm);
+        self
+    }
+
+    /// Records every counted file into a `Snapshot`, hashing each one with
+    /// the selected algorithm when `hashes` is set.
+    pub fn with_snapshot(mut self, hashes: bool) -> Self {
+        self.options.snapshot = true;
+        self.options.snapshot_hashes = hashes;
+        self
+    }
+
+    /// Records every visited entry into an inventory for export, hashing
+    /// regular files with the selected algorithm when `hashes` is set.
+    pub fn with_inventory(mut self, hashes: bool) -> Self {
+        self.options.inventory = true;
+        self.options.inventory_hashes = hashes;
+        self
+    }
+
+    /// Keeps the full directory hierarchy with sizes instead of only top-N.
+    pub fn with_size_tree(mut self) -> Self {
+        self.options.size_tree = true;
+        self
+    }
+
+    /// Picks the size that largest files and folders are ranked by.
+    pub fn with_usage(mut self, usage: SizeMetric) -> Self {
+        self.options.usage = usage;
+        self
+    }
+
+    /// Stops at mount points instead of descending into other filesystems.
+    pub fn with_one_file_system(mut self, one_file_system: bool) -> Self {
+        self.options.one_file_system = one_file_system;
+        self
+    }
+
+    /// Skips whatever `.gitignore`, `.ignore` and `.fsignore` files exclude.
+    pub fn with_respect_gitignore(mut self, respect_gitignore: bool) -> Self {
+        self.options.respect_gitignore = respect_gitignore;
+        self
+    }
+
+    /// Breaks usage down by extension and category.
+    pub fn with_by_type(mut self, by_type: bool) -> Self {
+        self.options.by_type = by_type;
+        self
+    }
+
+    /// Ranks largest folders by the bytes of one file type they hold.
+    pub fn with_folders_by_type(mut self, folders_by_type: Option<TypeSelector>) -> Self {
+        self.options.folders_by_type = folders_by_type;
+        self
+    }
+
+    /// Buckets files by the age of `field`, with `buckets` as boundaries.
+    pub fn with_age_histogram(mut self, field: TimeField, mut buckets: Vec<Duration>) -> Self {
+        buckets.sort();
+        buckets.dedup();
+        self.options.age_field = field;
+        self.options.age_buckets = Some(buckets);
+        self
+    }
+
+    /// Breaks usage down by owning user and group.
+    pub fn with_by_owner(mut self, by_owner: bool) -> Self {
+        self.options.by_owner = by_owner;
+        self
+    }
+
+    /// Reports scanning and hashing progress to `callback` while the
+    /// analysis runs.
+    pub fn with_progress(mut self, callback: impl Fn(&Progress) + Send + Sync + 'static) -> Self {
+        self.options.progress = Some(Arc::new(callback));
+        self
+    }
+
+    /// Stops the walk and any hashing once `token` is cancelled; the summary
+    /// then covers what was scanned and is marked incomplete.
+    pub fn with_cancel_token(mut self, token: CancelToken) -> Self {
+        self.options.cancel_token = Some(token);
+        self
+    }
+
+    /// Stops like a cancelled token once the analysis has run for `timeout`.
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.options.timeout = timeout;
+        self
+    }
+}
+
+fn compile_patterns(patterns: Vec<String>) -> Result<Vec<Pattern>, OptionsError> {
+    patterns
+        .into_iter()
+        .map(|pattern| {
+            Pattern::new(&pattern).map_err(|e| OptionsError::InvalidPattern {
+                reason: e.to_string(),
+                pattern,
+            })
+        })
+        .collect()
+}
+
+// Bytes, or a number followed by KB, MB, GB or TB (binary multiples)
+fn parse_min_size(input: &str) -> Result<u64, OptionsError> {
+    let input = input.trim();
+    if let Ok(bytes) = u64::from_str(input) {
+        return Ok(bytes);
+    }
+    let suffixes: [(&str, u64); 4] = [
+        ("KB", 1024),
+        ("MB", 1024 * 1024),
+        ("GB", 1024 * 1024 * 1024),
+        ("TB", 1024 * 1024 * 1024 * 1024),
+    ];
+    for (suffix, multiplier) in suffixes.iter() {
+        if let Some(number_part) = input.strip_suffix(suffix) {
+            return match number_part.trim().parse::<f64>() {
+                Ok(number) if number >= 0.0 => Ok((number * *multiplier as f64).round() as u64),
+                _ => Err(OptionsError::InvalidSize(input.to_string())),
+            };
+        }
+    }
+    Err(OptionsError::InvalidSize(input.to_string()))
+}
+
 // Ordering for heap operations
 impl Ord for FileInfoDirectory {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        other.size.cmp(&self.size) // Reverse order for max-heap
+        // Reverse order for max-heap, ties broken by path for stable results
+        other
+            .rank
+            .cmp(&self.rank)
+            .then_with(|| self.path.cmp(&other.path))
     }
 }
 
@@ -299,7 +887,11 @@ impl PartialOrd for FileInfoDirectory {
 
 impl Ord for FolderInfo {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        other.size.cmp(&self.size) // Reverse order for max-heap
+        // Reverse order for max-heap, ties broken by path for stable results
+        other
+            .rank
+            .cmp(&self.rank)
+            .then_with(|| self.path.cmp(&other.path))
     }
 }
 
@@ -309,10 +901,62 @@ impl PartialOrd for FolderInfo {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
 pub enum FileType {
     File,
     Dir,
+    #[serde(rename = "symlink")]
     Sym,
+    Socket,
+    Fifo,
+    Block,
+    Char,
+}
+
+impl FileType {
+    pub(crate) fn from_metadata(metadata: &Metadata) -> Option<Self> {
+        let file_type = metadata.file_type();
+        if file_type.is_file() {
+            return Some(FileType::File);
+        }
+        if file_type.is_dir() {
+            return Some(FileType::Dir);
+        }
+        if file_type.is_symlink() {
+            return Some(FileType::Sym);
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::FileTypeExt;
+            if file_type.is_socket() {
+                return Some(FileType::Socket);
+            }
+            if file_type.is_fifo() {
+                return Some(FileType::Fifo);
+            }
+            if file_type.is_block_device() {
+                return Some(FileType::Block);
+            }
+            if file_type.is_char_device() {
+                return Some(FileType::Char);
+            }
+        }
+        None
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FileType::File => "file",
+            FileType::Dir => "dir",
+            FileType::Sym => "symlink",
+            FileType::Socket => "socket",
+            FileType::Fifo => "fifo",
+            FileType::Block => "block",
+            FileType::Char => "char",
+        }
+    }
 }
 
 impl FromStr for FileType {
@@ -323,54 +967,709 @@ impl FromStr for FileType {
             "file" => Ok(FileType::File),
             "dir" | "directory" => Ok(FileType::Dir),
             "sym" | "symlink" | "link" => Ok(FileType::Sym),
+            "socket" | "sock" => Ok(FileType::Socket),
+            "fifo" | "pipe" => Ok(FileType::Fifo),
+            "block" => Ok(FileType::Block),
+            "char" => Ok(FileType::Char),
             _ => Err(format!(
-                "Invalid file type: '{}'. Valid values are: file, dir, sym",
+                "Invalid file type: '{}'. Valid values are: file, dir, symlink, socket, fifo, block, char",
                 s
             )),
         }
     }
 }
 
-pub struct SearchOptions {
-    path: PathBuf,
-    name_pattern: Vec<Pattern>,
-    content_pattern: Option<String>,
-    modified_after: Option<SystemTime>,
-    modified_before: Option<SystemTime>,
-    min_size: Option<u64>,
-    max_size: Option<u64>,
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum HashAlgorithm {
+    Sha256,
+    Blake3,
+    Xxh3,
 }
 
-impl SearchOptions {
-    pub fn new(
-        path: PathBuf,
-        name_pattern: Vec<Pattern>,
-        content_pattern: Option<String>,
-        modified_after: Option<SystemTime>,
-        modified_before: Option<SystemTime>,
-        min_size: Option<u64>,
-        max_size: Option<u64>,
-    ) -> Result<Self, String> {
-        // Convert name patterns with proper error handling
-
-        Ok(Self {
-            path,
-            name_pattern,
-            content_pattern,
-            modified_after,
-            modified_before,
-            min_size,
-            max_size,
-        })
-    }
+impl FromStr for HashAlgorithm {
+    type Err = String;
 
-    // Getters
-    pub fn path(&self) -> &PathBuf {
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "sha256" | "sha-256" => Ok(HashAlgorithm::Sha256),
+            "blake3" => Ok(HashAlgorithm::Blake3),
+            "xxh3" | "xxhash" => Ok(HashAlgorithm::Xxh3),
+            _ => Err(format!(
+                "Invalid hash algorithm: '{}'. Valid values are: sha256, blake3, xxh3",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum FileCategory {
+    Media,
+    Archive,
+    Source,
+    Document,
+    Log,
+    Build,
+    Other,
+}
+
+const MEDIA_EXTENSIONS: &[&str] = &[
+    "jpg", "jpeg", "png", "gif", "bmp", "tiff", "webp", "heic", "raw", "svg", "mp3", "flac",
+    "wav", "ogg", "aac", "m4a", "mp4", "mkv", "avi", "mov", "webm", "wmv", "m4v",
+];
+const ARCHIVE_EXTENSIONS: &[&str] = &[
+    "zip", "tar", "gz", "tgz", "bz2", "xz", "zst", "7z", "rar", "iso", "dmg", "img", "deb",
+    "rpm", "jar",
+];
+const SOURCE_EXTENSIONS: &[&str] = &[
+    "rs", "c", "h", "cpp", "hpp", "cc", "go", "py", "js", "ts", "jsx", "tsx", "java", "kt",
+    "swift", "rb", "php", "cs", "sh", "html", "css", "scss", "sql", "toml", "yaml", "yml",
+    "json", "xml",
+];
+const DOCUMENT_EXTENSIONS: &[&str] = &[
+    "pdf", "doc", "docx", "xls", "xlsx", "ppt", "pptx", "odt", "ods", "txt", "md", "rtf",
+    "csv", "epub",
+];
+const LOG_EXTENSIONS: &[&str] = &["log", "out", "err", "trace"];
+const BUILD_EXTENSIONS: &[&str] = &[
+    "o", "obj", "a", "so", "dylib", "dll", "lib", "class", "pyc", "pyo", "rlib", "rmeta", "d",
+    "wasm",
+];
+// Directories whose whole content counts as build output
+const BUILD_DIRECTORIES: &[&str] = &[
+    "target", "build", "dist", "node_modules", "__pycache__", ".gradle", ".next",
+];
+
+impl FileCategory {
+    /// Classifies a file by the directories it sits in below the scan
+    /// `root`, then its extension (`log.1`-style rotations count as logs).
+    /// Directories above `root` don't count, so scanning inside a `build`
+    /// directory doesn't make everything build output.
+    pub fn of(root: &Path, path: &Path) -> Self {
+        let relative = path.strip_prefix(root).unwrap_or(path);
+        let in_build_directory = relative.parent().is_some_and(|parent| {
+            parent.components().any(|component| {
+                BUILD_DIRECTORIES.contains(&component.as_os_str().to_string_lossy().as_ref())
+            })
+        });
+        if in_build_directory {
+            return FileCategory::Build;
+        }
+
+        let extension = extension_key(path);
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let lists = [
+            (MEDIA_EXTENSIONS, FileCategory::Media),
+            (ARCHIVE_EXTENSIONS, FileCategory::Archive),
+            (SOURCE_EXTENSIONS, FileCategory::Source),
+            (DOCUMENT_EXTENSIONS, FileCategory::Document),
+            (LOG_EXTENSIONS, FileCategory::Log),
+            (BUILD_EXTENSIONS, FileCategory::Build),
+        ];
+        for (extensions, category) in lists {
+            if extensions.contains(&extension.as_str()) {
+                return category;
+            }
+        }
+        if name.contains(".log.") {
+            return FileCategory::Log;
+        }
+        FileCategory::Other
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FileCategory::Media => "media",
+            FileCategory::Archive => "archive",
+            FileCategory::Source => "source",
+            FileCategory::Document => "document",
+            FileCategory::Log => "log",
+            FileCategory::Build => "build",
+            FileCategory::Other => "other",
+        }
+    }
+}
+
+impl FromStr for FileCategory {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "media" => Ok(FileCategory::Media),
+            "archive" | "archives" => Ok(FileCategory::Archive),
+            "source" => Ok(FileCategory::Source),
+            "document" | "documents" | "docs" => Ok(FileCategory::Document),
+            "log" | "logs" => Ok(FileCategory::Log),
+            "build" => Ok(FileCategory::Build),
+            "other" => Ok(FileCategory::Other),
+            _ => Err(format!(
+                "Invalid category: '{}'. Valid values are: media, archive, source, document, log, build, other",
+                s
+            )),
+        }
+    }
+}
+
+/// Lowercase extension used to group files, `(none)` without one.
+pub fn extension_key(path: &Path) -> String {
+    path.extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_else(|| "(none)".to_string())
+}
+
+/// A file type to rank folders by: a category name or an extension.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TypeSelector {
+    Category(FileCategory),
+    Extension(String),
+}
+
+impl TypeSelector {
+    /// Whether the file at `path`, found scanning `root`, is of this type.
+    pub fn matches(&self, root: &Path, path: &Path) -> bool {
+        match self {
+            TypeSelector::Category(category) => FileCategory::of(root, path) == *category,
+            TypeSelector::Extension(extension) => extension_key(path) == *extension,
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            TypeSelector::Category(category) => category.as_str().to_string(),
+            TypeSelector::Extension(extension) => format!(".{}", extension),
+        }
+    }
+}
+
+impl FromStr for TypeSelector {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(extension) = s.strip_prefix('.') {
+            return Ok(TypeSelector::Extension(extension.to_lowercase()));
+        }
+        match FileCategory::from_str(s) {
+            Ok(category) => Ok(TypeSelector::Category(category)),
+            Err(_) if !s.is_empty() => Ok(TypeSelector::Extension(s.to_lowercase())),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Bytes and file count for one extension or category.
+#[derive(Debug, Serialize)]
+pub struct TypeUsage {
+    name: String,
+    size: u64,
+    count: u64,
+}
+
+impl TypeUsage {
+    pub(crate) fn new(name: String, size: u64, count: u64) -> Self {
+        TypeUsage { name, size, count }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn size(&self) -> &u64 {
+        &self.size
+    }
+
+    pub fn count(&self) -> &u64 {
+        &self.count
+    }
+}
+
+/// The "Usage by type" section: largest extensions and every category.
+#[derive(Debug, Serialize)]
+pub struct TypeBreakdown {
+    extensions: Vec<TypeUsage>,
+    categories: Vec<TypeUsage>,
+}
+
+impl TypeBreakdown {
+    pub(crate) fn new(extensions: Vec<TypeUsage>, categories: Vec<TypeUsage>) -> Self {
+        TypeBreakdown {
+            extensions,
+            categories,
+        }
+    }
+
+    pub fn extensions(&self) -> &Vec<TypeUsage> {
+        &self.extensions
+    }
+
+    pub fn categories(&self) -> &Vec<TypeUsage> {
+        &self.categories
+    }
+}
+
+/// Bytes and file count owned by one uid or gid; for users, also the
+/// folders holding most of their data.
+#[derive(Debug, Serialize)]
+pub struct OwnerUsage {
+    id: u32,
+    name: Option<String>,
+    size: u64,
+    count: u64,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    largest_folders: Vec<FolderInfo>,
+}
+
+impl OwnerUsage {
+    pub(crate) fn new(id: u32, name: Option<String>, size: u64, count: u64) -> Self {
+        OwnerUsage {
+            id,
+            name,
+            size,
+            count,
+            largest_folders: Vec::new(),
+        }
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn name(&self) -> &Option<String> {
+        &self.name
+    }
+
+    pub fn size(&self) -> &u64 {
+        &self.size
+    }
+
+    pub fn count(&self) -> &u64 {
+        &self.count
+    }
+
+    pub fn largest_folders(&self) -> &Vec<FolderInfo> {
+        &self.largest_folders
+    }
+
+    pub(crate) fn set_largest_folders(&mut self, folders: Vec<FolderInfo>) {
+        self.largest_folders = folders;
+    }
+}
+
+/// Usage by owning user and group, largest first.
+#[derive(Debug, Serialize)]
+pub struct OwnerBreakdown {
+    users: Vec<OwnerUsage>,
+    groups: Vec<OwnerUsage>,
+}
+
+impl OwnerBreakdown {
+    pub(crate) fn new(users: Vec<OwnerUsage>, groups: Vec<OwnerUsage>) -> Self {
+        OwnerBreakdown { users, groups }
+    }
+
+    pub fn users(&self) -> &Vec<OwnerUsage> {
+        &self.users
+    }
+
+    pub fn groups(&self) -> &Vec<OwnerUsage> {
+        &self.groups
+    }
+}
+
+/// Which timestamp a file's age is measured from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum TimeField {
+    Mtime,
+    Atime,
+    Ctime,
+}
+
+impl TimeField {
+    /// The timestamp from `metadata`; `ctime` falls back to the modification
+    /// time where the platform has no inode change time.
+    pub fn of(&self, metadata: &Metadata) -> Option<SystemTime> {
+        match self {
+            TimeField::Mtime => metadata.modified().ok(),
+            TimeField::Atime => metadata.accessed().ok(),
+            TimeField::Ctime => utils::changed_time(metadata),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TimeField::Mtime => "mtime",
+            TimeField::Atime => "atime",
+            TimeField::Ctime => "ctime",
+        }
+    }
+}
+
+impl FromStr for TimeField {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "mtime" | "modified" => Ok(TimeField::Mtime),
+            "atime" | "accessed" => Ok(TimeField::Atime),
+            "ctime" | "changed" => Ok(TimeField::Ctime),
+            _ => Err(format!(
+                "Invalid time field: '{}'. Valid values are: mtime, atime, ctime",
+                s
+            )),
+        }
+    }
+}
+
+/// Files (and their bytes) whose age falls below `max_age`, and above the
+/// previous bucket's; the last bucket has no upper bound.
+#[derive(Debug, Serialize)]
+pub struct AgeBucket {
+    #[serde(serialize_with = "utils::serialize_age")]
+    max_age: Option<Duration>,
+    size: u64,
+    count: u64,
+}
+
+impl AgeBucket {
+    pub(crate) fn new(max_age: Option<Duration>) -> Self {
+        AgeBucket {
+            max_age,
+            size: 0,
+            count: 0,
+        }
+    }
+
+    pub fn max_age(&self) -> &Option<Duration> {
+        &self.max_age
+    }
+
+    pub fn size(&self) -> &u64 {
+        &self.size
+    }
+
+    pub fn count(&self) -> &u64 {
+        &self.count
+    }
+}
+
+/// Bytes and file counts by age, plus the largest files older than the last
+/// bucket boundary.
+#[derive(Debug, Serialize)]
+pub struct AgeHistogram {
+    field: TimeField,
+    buckets: Vec<AgeBucket>,
+    stale_files: Vec<FileInfoDirectory>,
+}
+
+impl AgeHistogram {
+    /// One bucket per boundary (sorted ascending) plus one for anything older.
+    pub(crate) fn new(field: TimeField, boundaries: &[Duration]) -> Self {
+        let mut buckets: Vec<AgeBucket> = boundaries
+            .iter()
+            .map(|boundary| AgeBucket::new(Some(*boundary)))
+            .collect();
+        buckets.push(AgeBucket::new(None));
+        AgeHistogram {
+            field,
+            buckets,
+            stale_files: Vec::new(),
+        }
+    }
+
+    pub fn field(&self) -> TimeField {
+        self.field
+    }
+
+    pub fn buckets(&self) -> &Vec<AgeBucket> {
+        &self.buckets
+    }
+
+    pub fn stale_files(&self) -> &Vec<FileInfoDirectory> {
+        &self.stale_files
+    }
+
+    /// Counts a file of `age`; returns whether it is stale (in the last,
+    /// unbounded bucket).
+    pub(crate) fn add(&mut self, age: Duration, size: u64) -> bool {
+        let last = self.buckets.len() - 1;
+        let index = self
+            .buckets
+            .iter()
+            .position(|bucket| bucket.max_age.is_some_and(|max_age| age < max_age))
+            .unwrap_or(last);
+        self.buckets[index].size += size;
+        self.buckets[index].count += 1;
+        index == last
+    }
+
+    pub(crate) fn set_stale_files(&mut self, stale_files: Vec<FileInfoDirectory>) {
+        self.stale_files = stale_files;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum SizeMetric {
+    // File length, as reported by `ls -l`
+    Apparent,
+    // Allocated blocks, as reported by `du`
+    Disk,
+}
+
+impl FromStr for SizeMetric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "apparent" => Ok(SizeMetric::Apparent),
+            "disk" => Ok(SizeMetric::Disk),
+            _ => Err(format!(
+                "Invalid usage: '{}'. Valid values are: apparent, disk",
+                s
+            )),
+        }
+    }
+}
+
+/// How the content pattern is matched; start from
+/// `ContentMatchOptions::default()`, a case-sensitive fixed string.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ContentMatchOptions {
+    regex: bool,
+    case_insensitive: bool,
+    whole_word: bool,
+    max_count: Option<usize>,
+    before_context: usize,
+    after_context: usize,
+    binary: bool,
+}
+
+impl ContentMatchOptions {
+    /// Treats the pattern as a regular expression instead of a fixed string.
+    pub fn with_regex(mut self, regex: bool) -> Self {
+        self.regex = regex;
+        self
+    }
+
+    pub fn with_case_insensitive(mut self, case_insensitive: bool) -> Self {
+        self.case_insensitive = case_insensitive;
+        self
+    }
+
+    /// Only matches the pattern at word boundaries.
+    pub fn with_whole_word(mut self, whole_word: bool) -> Self {
+        self.whole_word = whole_word;
+        self
+    }
+
+    /// Stops reading a file after `max_count` matching lines.
+    pub fn with_max_count(mut self, max_count: Option<usize>) -> Self {
+        self.max_count = max_count;
+        self
+    }
+
+    pub fn with_context(mut self, before: usize, after: usize) -> Self {
+        self.before_context = before;
+        self.after_context = after;
+        self
+    }
+
+    /// Searches binary files too, reporting byte offsets instead of lines.
+    pub fn with_binary(mut self, binary: bool) -> Self {
+        self.binary = binary;
+        self
+    }
+
+    pub fn regex(&self) -> bool {
+        self.regex
+    }
+    pub fn case_insensitive(&self) -> bool {
+        self.case_insensitive
+    }
+    pub fn whole_word(&self) -> bool {
+        self.whole_word
+    }
+    pub fn max_count(&self) -> Option<usize> {
+        self.max_count
+    }
+    pub fn before_context(&self) -> usize {
+        self.before_context
+    }
+    pub fn after_context(&self) -> usize {
+        self.after_context
+    }
+    pub fn binary(&self) -> bool {
+        self.binary
+    }
+
+    /// Compiles `pattern` into a matcher; fixed strings are escaped so the
+    /// same engine handles every mode. The byte pattern is only built for
+    /// binary search.
+    pub fn build_matcher(&self, pattern: &str) -> Result<ContentMatcher, regex::Error> {
+        let pattern = if self.regex {
+            pattern.to_string()
+        } else {
+            regex::escape(pattern)
+        };
+        let text = RegexBuilder::new(&pattern)
+            .case_insensitive(self.case_insensitive)
+            .build()?;
+        let bytes = self
+            .binary
+            .then(|| {
+                bytes::RegexBuilder::new(&pattern)
+                    .case_insensitive(self.case_insensitive)
+                    .build()
+            })
+            .transpose()?;
+        Ok(ContentMatcher {
+            text,
+            bytes,
+            whole_word: self.whole_word,
+        })
+    }
+}
+
+/// The content pattern compiled for text lines and, with `--binary`, for raw
+/// binary data.
+///
+/// Whole-word matching checks the characters around each match rather than
+/// wrapping the pattern in `\b`, so patterns that start or end with
+/// punctuation, like `foo(`, still match when followed by a space.
+pub struct ContentMatcher {
+    text: Regex,
+    bytes: Option<bytes::Regex>,
+    whole_word: bool,
+}
+
+impl ContentMatcher {
+    /// Byte range of the first match in `line`.
+    pub fn find(&self, line: &str) -> Option<Range<usize>> {
+        let mut at = 0;
+        while at <= line.len() {
+            let found = self.text.find_at(line, at)?;
+            let before = line[..found.start()].chars().next_back();
+            let after = line[found.end()..].chars().next();
+            let joined = before.is_some_and(is_word_char) || after.is_some_and(is_word_char);
+            if !self.whole_word || !joined {
+                return Some(found.range());
+            }
+            at = found.start() + line[found.start()..].chars().next().map_or(1, char::len_utf8);
+        }
+        None
+    }
+
+    /// Start offsets of every match in `haystack`.
+    ///
+    /// # Panics
+    ///
+    /// If the matcher was built without binary search.
+    pub fn find_bytes<'a>(&'a self, haystack: &'a [u8]) -> impl Iterator<Item = usize> + 'a {
+        let bytes = self
+            .bytes
+            .as_ref()
+            .expect("content matcher built without binary search");
+        let is_word_byte = |byte: &u8| byte.is_ascii_alphanumeric() || *byte == b'_';
+        let mut at = 0;
+        std::iter::from_fn(move || {
+            while at <= haystack.len() {
+                let found = bytes.find_at(haystack, at)?;
+                let before = found.start().checked_sub(1).map(|i| haystack[i]);
+                let after = haystack.get(found.end());
+                let joined =
+                    before.as_ref().is_some_and(is_word_byte) || after.is_some_and(is_word_byte);
+                if self.whole_word && joined {
+                    at = found.start() + 1;
+                    continue;
+                }
+                at = found.end().max(found.start() + 1);
+                return Some(found.start());
+            }
+            None
+        })
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Outcome of matching a file's content against the content pattern.
+#[non_exhaustive]
+pub enum ContentMatch {
+    // No content pattern was given
+    Unfiltered,
+    Lines(Vec<LineMatch>),
+    // Byte offsets of matches in a binary file
+    Binary(Vec<u64>),
+    SkippedBinary,
+    NoMatch,
+}
+
+pub struct SearchOptions {
+    path: PathBuf,
+    name_pattern: Vec<Pattern>,
+    content_pattern: Option<String>,
+    content_matcher: Option<ContentMatcher>,
+    content_match_options: ContentMatchOptions,
+    modified_after: Option<SystemTime>,
+    modified_before: Option<SystemTime>,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    filter: Option<Filter>,
+    file_types: Vec<FileType>,
+    one_file_system: bool,
+    respect_gitignore: bool,
+    threads: usize,
+    progress: Option<ProgressCallback>,
+}
+
+impl SearchOptions {
+    /// Starts building options for searching below `path`.
+    pub fn builder(path: PathBuf) -> SearchOptionsBuilder {
+        SearchOptionsBuilder {
+            options: SearchOptions {
+                path,
+                name_pattern: Vec::new(),
+                content_pattern: None,
+                content_matcher: None,
+                content_match_options: ContentMatchOptions::default(),
+                modified_after: None,
+                modified_before: None,
+                min_size: None,
+                max_size: None,
+                filter: None,
+                file_types: vec![FileType::File],
+                one_file_system: false,
+                respect_gitignore: false,
+                threads: 0,
+                progress: None,
+            },
+            name_patterns: Vec::new(),
+            filter: None,
+        }
+    }
+
+    // Getters
+    pub fn path(&self) -> &PathBuf {
         &self.path
     }
     pub fn content_pattern(&self) -> &Option<String> {
         &self.content_pattern
     }
+    pub fn content_match_options(&self) -> &ContentMatchOptions {
+        &self.content_match_options
+    }
     pub fn name_pattern(&self) -> &Vec<Pattern> {
         &self.name_pattern
     }
@@ -386,6 +1685,28 @@ impl SearchOptions {
     pub fn max_size(&self) -> &Option<u64> {
         &self.max_size
     }
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+    pub fn filter(&self) -> &Option<Filter> {
+        &self.filter
+    }
+    pub fn file_types(&self) -> &Vec<FileType> {
+        &self.file_types
+    }
+    pub fn one_file_system(&self) -> bool {
+        self.one_file_system
+    }
+    pub fn respect_gitignore(&self) -> bool {
+        self.respect_gitignore
+    }
+    pub fn progress(&self) -> Option<&ProgressCallback> {
+        self.progress.as_ref()
+    }
+
+    pub fn match_file_type(&self, file_type: FileType) -> bool {
+        self.file_types.contains(&file_type)
+    }
 
     pub fn match_name_pattern(&self, path: &Path) -> bool {
         if self.name_pattern.is_empty() {
@@ -400,79 +1721,307 @@ impl SearchOptions {
         }
     }
 
-    pub fn match_content_pattern(&self, path: &Path) -> (bool, Option<(usize, String)>) {
-        match &self.content_pattern {
-            Some(content_pattern) => {
-                match utils::content_exists_in_file(path, content_pattern) {
-                    Ok(Some(line_info)) => (true, Some(line_info)),
-                    Ok(None) => (false, None),
-                    Err(_) => (false, None), // Treat errors as no match
-                }
+    pub fn match_content_pattern(&self, path: &Path) -> ContentMatch {
+        match &self.content_matcher {
+            Some(content_matcher) => {
+                utils::content_matches_in_file(path, content_matcher, &self.content_match_options)
+                    .unwrap_or(ContentMatch::NoMatch) // Treat errors as no match
             }
-            None => (true, None), // No content pattern means
+            None => ContentMatch::Unfiltered,
         }
     }
 
+    pub fn match_filter(&self, root: &Path, path: &Path, metadata: &Metadata) -> bool {
+        self.filter
+            .as_ref()
+            .is_none_or(|filter| filter.matches(root, path, metadata))
+    }
+
     pub fn match_modified_date(&self, modified_time: &SystemTime) -> bool {
-        match &self.modified_after {
-            Some(time) => {
-                if !(time < modified_time) {
-                    return false;
-                }
+        if let Some(time) = &self.modified_after {
+            if time >= modified_time {
+                return false;
             }
-            None => {}
         }
-        match &self.modified_before {
-            Some(time) => {
-                if !(time > modified_time) {
-                    return false;
-                }
+        if let Some(time) = &self.modified_before {
+            if time <= modified_time {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    pub fn match_size(&self, size: &u64) -> bool {
+        if let Some(min_size) = &self.min_size {
+            if min_size >= size {
+                return false;
+            }
+        }
+        if let Some(max_size) = &self.max_size {
+            if size >= max_size {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+/// Collects settings for [`SearchOptions`]; patterns, the content regex and
+/// the filter expression are compiled and checked in [`build`](Self::build).
+pub struct SearchOptionsBuilder {
+    options: SearchOptions,
+    // Kept as given until build()
+    name_patterns: Vec<String>,
+    filter: Option<String>,
+}
+
+impl SearchOptionsBuilder {
+    pub fn build(self) -> Result<SearchOptions, OptionsError> {
+        let options = self.options;
+        if let (Some(min_size), Some(max_size)) = (options.min_size, options.max_size) {
+            if min_size > max_size {
+                return Err(OptionsError::Conflict(
+                    "the minimum size is larger than the maximum size",
+                ));
+            }
+        }
+        if let (Some(after), Some(before)) = (options.modified_after, options.modified_before) {
+            if after >= before {
+                return Err(OptionsError::Conflict(
+                    "the modified-after date is not before the modified-before date",
+                ));
             }
-            None => {}
         }
+        if options.content_pattern.is_none()
+            && options.content_match_options != ContentMatchOptions::default()
+        {
+            return Err(OptionsError::Conflict(
+                "content match options need a content pattern",
+            ));
+        }
+
+        let name_pattern = compile_patterns(self.name_patterns)?;
+        let filter = self
+            .filter
+            .as_deref()
+            .map(Filter::parse)
+            .transpose()
+            .map_err(OptionsError::InvalidFilter)?;
+        let content_matcher = options
+            .content_pattern
+            .as_deref()
+            .map(|pattern| {
+                options
+                    .content_match_options
+                    .build_matcher(pattern)
+                    .map_err(|e| OptionsError::InvalidRegex {
+                        pattern: pattern.to_string(),
+                        reason: e.to_string(),
+                    })
+            })
+            .transpose()?;
+        Ok(SearchOptions {
+            name_pattern,
+            filter,
+            content_matcher,
+            ..options
+        })
+    }
+
+    /// Keeps only paths matching at least one of these globs.
+    pub fn with_name_patterns(mut self, name_patterns: Vec<String>) -> Self {
+        self.name_patterns = name_patterns;
+        self
+    }
+
+    /// Keeps only files whose content matches `content_pattern`.
+    pub fn with_content_pattern(mut self, content_pattern: Option<String>) -> Self {
+        self.options.content_pattern = content_pattern;
+        self
+    }
+
+    pub fn with_content_match_options(mut self, content_match_options: ContentMatchOptions) -> Self {
+        self.options.content_match_options = content_match_options;
+        self
+    }
+
+    pub fn with_modified_after(mut self, modified_after: Option<SystemTime>) -> Self {
+        self.options.modified_after = modified_after;
+        self
+    }
+
+    pub fn with_modified_before(mut self, modified_before: Option<SystemTime>) -> Self {
+        self.options.modified_before = modified_before;
+        self
+    }
+
+    pub fn with_min_size(mut self, min_size: Option<u64>) -> Self {
+        self.options.min_size = min_size;
+        self
+    }
+
+    pub fn with_max_size(mut self, max_size: Option<u64>) -> Self {
+        self.options.max_size = max_size;
+        self
+    }
+
+    /// Sets the number of walker threads, `0` picks one per core.
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.options.threads = threads;
+        self
+    }
+
+    /// Sets a `--where` expression checked on top of the other criteria.
+    pub fn with_filter(mut self, filter: Option<String>) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Restricts results to the given entry types; regular files only when
+    /// the list is empty.
+    pub fn with_file_types(mut self, file_types: Vec<FileType>) -> Self {
+        self.options.file_types = if file_types.is_empty() {
+            vec![FileType::File]
+        } else {
+            file_types
+        };
+        self
+    }
+
+    /// Stops at mount points instead of descending into other filesystems.
+    pub fn with_one_file_system(mut self, one_file_system: bool) -> Self {
+        self.options.one_file_system = one_file_system;
+        self
+    }
+
+    /// Skips whatever `.gitignore`, `.ignore` and `.fsignore` files exclude.
+    pub fn with_respect_gitignore(mut self, respect_gitignore: bool) -> Self {
+        self.options.respect_gitignore = respect_gitignore;
+        self
+    }
+
+    /// Reports how many entries have been searched to `callback` while the
+    /// search runs.
+    pub fn with_progress(mut self, callback: impl Fn(&Progress) + Send + Sync + 'static) -> Self {
+        self.options.progress = Some(Arc::new(callback));
+        self
+    }
+}
 
-        return true;
+#[derive(Debug, Serialize)]
+pub struct ContextLine {
+    line_number: usize,
+    line: String,
+}
+
+impl ContextLine {
+    pub(crate) fn new(line_number: usize, line: String) -> Self {
+        ContextLine { line_number, line }
     }
 
-    pub fn match_size(&self, size: &u64) -> bool {
-        match &self.min_size {
-            Some(min_size) => {
-                if !(min_size < size) {
-                    return false;
-                }
-            }
-            None => {}
-        }
-        match &self.max_size {
-            Some(max_size) => {
-                if !(size < max_size) {
-                    return false;
-                }
-            }
-            None => {}
+    pub fn line_number(&self) -> usize {
+        self.line_number
+    }
+    pub fn line(&self) -> &str {
+        &self.line
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct LineMatch {
+    line_number: usize,
+    // 1-based byte offset of the first match in the original line
+    column: usize,
+    line: String,
+    // Byte range of the match within `line`
+    match_start: usize,
+    match_end: usize,
+    context_before: Vec<ContextLine>,
+    context_after: Vec<ContextLine>,
+}
+
+impl LineMatch {
+    pub(crate) fn new(
+        line_number: usize,
+        column: usize,
+        line: String,
+        match_start: usize,
+        match_end: usize,
+    ) -> Self {
+        LineMatch {
+            line_number,
+            column,
+            line,
+            match_start,
+            match_end,
+            context_before: Vec::new(),
+            context_after: Vec::new(),
         }
+    }
 
-        return true;
+    pub fn line_number(&self) -> usize {
+        self.line_number
+    }
+    pub fn column(&self) -> usize {
+        self.column
+    }
+    pub fn line(&self) -> &str {
+        &self.line
+    }
+    pub fn match_start(&self) -> usize {
+        self.match_start
+    }
+    pub fn match_end(&self) -> usize {
+        self.match_end
+    }
+    pub fn context_before(&self) -> &Vec<ContextLine> {
+        &self.context_before
+    }
+    pub fn context_after(&self) -> &Vec<ContextLine> {
+        &self.context_after
+    }
+
+    pub(crate) fn set_context_before(&mut self, context_before: Vec<ContextLine>) {
+        self.context_before = context_before;
+    }
+    pub(crate) fn push_context_after(&mut self, line: ContextLine) {
+        self.context_after.push(line);
     }
 }
 
+#[derive(Serialize)]
 pub struct FileInfoSearch {
+    #[serde(serialize_with = "utils::serialize_path")]
     path: PathBuf,
+    file_type: FileType,
     size: u64,
-    content: Option<(usize, String)>,
+    match_count: usize,
+    matches: Vec<LineMatch>,
+    binary: bool,
+    // Byte offsets of matches when `binary` is set
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    binary_offsets: Vec<u64>,
+    #[serde(serialize_with = "utils::serialize_rfc3339")]
     modified_date: SystemTime,
 }
 impl FileInfoSearch {
-    pub fn new(
+    pub(crate) fn new(
         path: PathBuf,
+        file_type: FileType,
         size: u64,
-        content: Option<(usize, String)>,
+        matches: Vec<LineMatch>,
         modified_date: SystemTime,
     ) -> Self {
         FileInfoSearch {
             path,
+            file_type,
             size,
-            content,
+            match_count: matches.len(),
+            matches,
+            binary: false,
+            binary_offsets: Vec::new(),
             modified_date,
         }
     }
@@ -480,25 +2029,50 @@ impl FileInfoSearch {
     pub fn path(&self) -> &PathBuf {
         &self.path
     }
+    pub fn file_type(&self) -> FileType {
+        self.file_type
+    }
     pub fn size(&self) -> &u64 {
         &self.size
     }
     pub fn modified_date(&self) -> &SystemTime {
         &self.modified_date
     }
-    pub fn content(&self) -> &Option<(usize, String)> {
-        &self.content
+    pub fn matches(&self) -> &Vec<LineMatch> {
+        &self.matches
+    }
+    pub fn match_count(&self) -> usize {
+        self.match_count
+    }
+    pub fn binary(&self) -> bool {
+        self.binary
+    }
+    pub fn binary_offsets(&self) -> &Vec<u64> {
+        &self.binary_offsets
+    }
+
+    pub(crate) fn set_binary_offsets(&mut self, binary_offsets: Vec<u64>) {
+        self.binary = true;
+        self.match_count = binary_offsets.len();
+        self.binary_offsets = binary_offsets;
     }
 }
 
+#[derive(Serialize)]
 pub struct SearchResult {
     total_size: u64,
+    #[serde(rename = "files_searched")]
     file_searched: u64,
+    files_found: u64,
+    binary_skipped: u64,
+    #[serde(rename = "search_time_ms", serialize_with = "utils::serialize_duration_ms")]
     search_time: std::time::Duration,
+    #[serde(rename = "files")]
     files_result: Vec<FileInfoSearch>,
+    mount_points: Vec<MountPoint>,
 }
 impl SearchResult {
-    pub fn new(
+    pub(crate) fn new(
         total_size: u64,
         file_searched: u64,
         search_time: std::time::Duration,
@@ -507,8 +2081,11 @@ impl SearchResult {
         SearchResult {
             total_size,
             file_searched,
+            files_found: 0,
+            binary_skipped: 0,
             search_time,
             files_result,
+            mount_points: Vec::new(),
         }
     }
     pub fn files_result(&self) -> &Vec<FileInfoSearch> {
@@ -523,20 +2100,713 @@ impl SearchResult {
     pub fn file_searched(&self) -> &u64 {
         &self.file_searched
     }
+    /// Number of matches, also when they were streamed rather than kept.
+    pub fn files_found(&self) -> &u64 {
+        &self.files_found
+    }
+    pub fn binary_skipped(&self) -> &u64 {
+        &self.binary_skipped
+    }
+    pub fn mount_points(&self) -> &Vec<MountPoint> {
+        &self.mount_points
+    }
+
+    pub(crate) fn set_mount_points(&mut self, mount_points: Vec<MountPoint>) {
+        self.mount_points = mount_points;
+    }
 
-    pub fn add_to_total_size(&mut self, size: u64) {
+    pub(crate) fn add_to_total_size(&mut self, size: u64) {
         self.total_size += size;
     }
 
-    pub fn increment_file_searched(&mut self) {
+    pub(crate) fn increment_file_searched(&mut self) {
         self.file_searched += 1;
     }
 
-    pub fn set_files_result(&mut self, files_result: Vec<FileInfoSearch>) {
+    pub(crate) fn increment_files_found(&mut self) {
+        self.files_found += 1;
+    }
+
+    pub(crate) fn increment_binary_skipped(&mut self) {
+        self.binary_skipped += 1;
+    }
+
+    pub(crate) fn set_files_result(&mut self, files_result: Vec<FileInfoSearch>) {
         self.files_result = files_result;
     }
 
-    pub fn set_duration(&mut self, duration: std::time::Duration) {
+    pub(crate) fn set_duration(&mut self, duration: std::time::Duration) {
         self.search_time = duration;
     }
 }
+
+/// Receives [`Progress`] updates during a scan; set with `with_progress` on
+/// the option builders.
+///
+/// It is called from the scanning thread and, while hashing, from the hash
+/// workers, at most every few hundred milliseconds plus once when each phase
+/// ends.
+pub type ProgressCallback = Arc<dyn Fn(&Progress) + Send + Sync>;
+
+/// What a scan is busy with when it reports [`Progress`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ProgressPhase {
+    /// Walking the tree
+    Scanning,
+    /// Hashing files, for duplicates, snapshot or inventory hashes
+    Hashing,
+}
+
+/// How far a running scan has got.
+#[derive(Debug, Clone)]
+pub struct Progress {
+    phase: ProgressPhase,
+    entries: u64,
+    bytes: u64,
+    matches: u64,
+    current_dir: Option<PathBuf>,
+    files_hashed: u64,
+    bytes_hashed: u64,
+    files_to_hash: u64,
+    bytes_to_hash: u64,
+    elapsed: Duration,
+}
+
+impl Progress {
+    pub(crate) fn scanning(
+        entries: u64,
+        bytes: u64,
+        matches: u64,
+        current_dir: Option<PathBuf>,
+        elapsed: Duration,
+    ) -> Self {
+        Progress {
+            phase: ProgressPhase::Scanning,
+            entries,
+            bytes,
+            matches,
+            current_dir,
+            files_hashed: 0,
+            bytes_hashed: 0,
+            files_to_hash: 0,
+            bytes_to_hash: 0,
+            elapsed,
+        }
+    }
+
+    pub(crate) fn hashing(
+        files_hashed: u64,
+        files_to_hash: u64,
+        bytes_hashed: u64,
+        bytes_to_hash: u64,
+        elapsed: Duration,
+    ) -> Self {
+        Progress {
+            phase: ProgressPhase::Hashing,
+            entries: 0,
+            bytes: 0,
+            matches: 0,
+            current_dir: None,
+            files_hashed,
+            bytes_hashed,
+            files_to_hash,
+            bytes_to_hash,
+            elapsed,
+        }
+    }
+
+    pub fn phase(&self) -> ProgressPhase {
+        self.phase
+    }
+
+    /// Entries scanned so far; for a search, entries of the wanted types.
+    pub fn entries(&self) -> u64 {
+        self.entries
+    }
+
+    /// Bytes of the files scanned so far.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Matches found so far, for a search.
+    pub fn matches(&self) -> u64 {
+        self.matches
+    }
+
+    /// The directory of the entry scanned last.
+    pub fn current_dir(&self) -> &Option<PathBuf> {
+        &self.current_dir
+    }
+
+    pub fn files_hashed(&self) -> u64 {
+        self.files_hashed
+    }
+
+    pub fn bytes_hashed(&self) -> u64 {
+        self.bytes_hashed
+    }
+
+    pub fn files_to_hash(&self) -> u64 {
+        self.files_to_hash
+    }
+
+    pub fn bytes_to_hash(&self) -> u64 {
+        self.bytes_to_hash
+    }
+
+    /// Time since the current phase started.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Estimated time left while hashing, from the byte rate so far.
+    pub fn eta(&self) -> Option<Duration> {
+        if self.phase != ProgressPhase::Hashing || self.bytes_hashed == 0 {
+            return None;
+        }
+        let rate = self.bytes_hashed as f64 / self.elapsed.as_secs_f64().max(0.001);
+        let remaining = self.bytes_to_hash.saturating_sub(self.bytes_hashed);
+        Some(Duration::from_secs_f64(remaining as f64 / rate))
+    }
+}
+
+/// Stops a running analysis early from another thread, e.g. a signal
+/// handler or a GUI's cancel button. Clones share the same flag.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        CancelToken::default()
+    }
+
+    /// Asks the analysis to stop; it returns what it has gathered so far.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Why an analysis stopped before covering the whole tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum StopReason {
+    /// The [`CancelToken`] was cancelled
+    Cancelled,
+    /// The timeout set with `with_timeout` ran out
+    TimedOut,
+}
+
+impl StopReason {
+    /// The name used in JSON output and exports.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StopReason::Cancelled => "cancelled",
+            StopReason::TimedOut => "timed_out",
+        }
+    }
+
+    /// The reason as it reads in messages ("scan timed out").
+    pub fn describe(&self) -> &'static str {
+        match self {
+            StopReason::Cancelled => "cancelled",
+            StopReason::TimedOut => "timed out",
+        }
+    }
+
+    /// The process exit code for a run that stopped this way, as for SIGINT
+    /// and `timeout(1)`.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            StopReason::Cancelled => 130,
+            StopReason::TimedOut => 124,
+        }
+    }
+}
+
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// One visited entry, as written by `--export-csv` / `--export-sqlite`.
+#[derive(Debug)]
+pub struct InventoryEntry {
+    path: PathBuf,
+    file_type: Option<FileType>,
+    size: u64,
+    disk_usage: u64,
+    modified: Option<SystemTime>,
+    owner: Option<(u32, u32)>,
+    permissions: Option<u32>,
+    hash: Option<String>,
+}
+
+impl InventoryEntry {
+    pub(crate) fn from_metadata(path: PathBuf, metadata: &Metadata) -> Self {
+        InventoryEntry {
+            path,
+            file_type: FileType::from_metadata(metadata),
+            size: metadata.len(),
+            disk_usage: utils::disk_usage(metadata),
+            modified: metadata.modified().ok(),
+            owner: utils::owner_ids(metadata),
+            permissions: utils::permissions(metadata),
+            hash: None,
+        }
+    }
+
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    pub fn file_type(&self) -> Option<FileType> {
+        self.file_type
+    }
+
+    pub fn size(&self) -> &u64 {
+        &self.size
+    }
+
+    /// Allocated size in 512-byte blocks, like `st_blocks`.
+    pub fn blocks(&self) -> u64 {
+        self.disk_usage / 512
+    }
+
+    pub fn modified(&self) -> &Option<SystemTime> {
+        &self.modified
+    }
+
+    pub fn owner(&self) -> Option<(u32, u32)> {
+        self.owner
+    }
+
+    pub fn permissions(&self) -> Option<u32> {
+        self.permissions
+    }
+
+    pub fn hash(&self) -> &Option<String> {
+        &self.hash
+    }
+
+    pub(crate) fn set_hash(&mut self, hash: Option<String>) {
+        self.hash = hash;
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SnapshotEntry {
+    // Relative to the snapshot root
+    #[serde(serialize_with = "utils::serialize_path")]
+    path: PathBuf,
+    size: u64,
+    #[serde(
+        serialize_with = "utils::serialize_rfc3339_precise",
+        deserialize_with = "utils::deserialize_rfc3339"
+    )]
+    modified: SystemTime,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    hash: Option<String>,
+}
+
+impl SnapshotEntry {
+    pub(crate) fn new(path: PathBuf, size: u64, modified: SystemTime) -> Self {
+        SnapshotEntry {
+            path,
+            size,
+            modified,
+            hash: None,
+        }
+    }
+
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+    pub fn size(&self) -> &u64 {
+        &self.size
+    }
+    pub fn modified(&self) -> &SystemTime {
+        &self.modified
+    }
+    pub fn hash(&self) -> &Option<String> {
+        &self.hash
+    }
+
+    pub(crate) fn set_hash(&mut self, hash: Option<String>) {
+        self.hash = hash;
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    version: u32,
+    #[serde(serialize_with = "utils::serialize_path")]
+    root: PathBuf,
+    #[serde(
+        serialize_with = "utils::serialize_rfc3339_precise",
+        deserialize_with = "utils::deserialize_rfc3339"
+    )]
+    created: SystemTime,
+    hash_algorithm: Option<HashAlgorithm>,
+    files: Vec<SnapshotEntry>,
+}
+
+impl Snapshot {
+    pub(crate) fn new(root: PathBuf, hash_algorithm: Option<HashAlgorithm>) -> Self {
+        Snapshot {
+            version: SNAPSHOT_VERSION,
+            root,
+            created: SystemTime::now(),
+            hash_algorithm,
+            files: Vec::new(),
+        }
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+    pub fn root(&self) -> &PathBuf {
+        &self.root
+    }
+    pub fn hash_algorithm(&self) -> Option<HashAlgorithm> {
+        self.hash_algorithm
+    }
+    pub fn files(&self) -> &Vec<SnapshotEntry> {
+        &self.files
+    }
+    pub(crate) fn files_mut(&mut self) -> &mut Vec<SnapshotEntry> {
+        &mut self.files
+    }
+
+    pub(crate) fn push(&mut self, entry: SnapshotEntry) {
+        self.files.push(entry);
+    }
+}
+
+pub struct DiffOptions {
+    old: PathBuf,
+    new: PathBuf,
+    top_n: usize,
+    ignore_patterns: Vec<Pattern>,
+    threads: usize,
+    cancel_token: Option<CancelToken>,
+}
+
+impl DiffOptions {
+    /// Starts a comparison of the `old` snapshot against `new`, a snapshot
+    /// file or a directory to scan.
+    pub fn builder(old: PathBuf, new: PathBuf) -> DiffOptionsBuilder {
+        DiffOptionsBuilder {
+            options: DiffOptions {
+                old,
+                new,
+                top_n: 10,
+                ignore_patterns: Vec::new(),
+                threads: 0,
+                cancel_token: None,
+            },
+            ignore_patterns: Vec::new(),
+        }
+    }
+
+    pub fn old(&self) -> &PathBuf {
+        &self.old
+    }
+    pub fn new_path(&self) -> &PathBuf {
+        &self.new
+    }
+    pub fn top_n(&self) -> usize {
+        self.top_n
+    }
+    pub fn ignore_patterns(&self) -> &Vec<Pattern> {
+        &self.ignore_patterns
+    }
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+    pub fn cancel_token(&self) -> Option<&CancelToken> {
+        self.cancel_token.as_ref()
+    }
+}
+
+/// Collects settings for [`DiffOptions`]; ignore patterns are compiled in
+/// [`build`](Self::build).
+pub struct DiffOptionsBuilder {
+    options: DiffOptions,
+    // Kept as given until build()
+    ignore_patterns: Vec<String>,
+}
+
+impl DiffOptionsBuilder {
+    pub fn build(self) -> Result<DiffOptions, OptionsError> {
+        Ok(DiffOptions {
+            ignore_patterns: compile_patterns(self.ignore_patterns)?,
+            ..self.options
+        })
+    }
+
+    /// Sets how many entries each list of changes keeps.
+    pub fn with_top_n(mut self, top_n: usize) -> Self {
+        self.options.top_n = top_n;
+        self
+    }
+
+    /// Skips paths matching any of these glob patterns when scanning a
+    /// directory.
+    pub fn with_ignore_patterns(mut self, ignore_patterns: Vec<String>) -> Self {
+        self.ignore_patterns = ignore_patterns;
+        self
+    }
+
+    /// Sets the number of walker threads, `0` picks one per core.
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.options.threads = threads;
+        self
+    }
+
+    /// Stops scanning a directory once `token` is cancelled; the diff then
+    /// fails with [`AnalysisError::Interrupted`](crate::AnalysisError::Interrupted)
+    /// rather than reporting the unscanned files as removed.
+    pub fn with_cancel_token(mut self, token: CancelToken) -> Self {
+        self.options.cancel_token = Some(token);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileChange {
+    #[serde(serialize_with = "utils::serialize_path")]
+    path: PathBuf,
+    old_size: Option<u64>,
+    new_size: Option<u64>,
+    delta: i64,
+}
+
+impl FileChange {
+    pub(crate) fn new(path: PathBuf, old_size: Option<u64>, new_size: Option<u64>) -> Self {
+        let delta = new_size.unwrap_or_default() as i64 - old_size.unwrap_or_default() as i64;
+        FileChange {
+            path,
+            old_size,
+            new_size,
+            delta,
+        }
+    }
+
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+    pub fn old_size(&self) -> &Option<u64> {
+        &self.old_size
+    }
+    pub fn new_size(&self) -> &Option<u64> {
+        &self.new_size
+    }
+    pub fn delta(&self) -> i64 {
+        self.delta
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct FolderDelta {
+    #[serde(serialize_with = "utils::serialize_path")]
+    path: PathBuf,
+    old_size: u64,
+    new_size: u64,
+    delta: i64,
+}
+
+impl FolderDelta {
+    pub(crate) fn new(path: PathBuf, old_size: u64, new_size: u64) -> Self {
+        FolderDelta {
+            path,
+            old_size,
+            new_size,
+            delta: new_size as i64 - old_size as i64,
+        }
+    }
+
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+    pub fn old_size(&self) -> &u64 {
+        &self.old_size
+    }
+    pub fn new_size(&self) -> &u64 {
+        &self.new_size
+    }
+    pub fn delta(&self) -> i64 {
+        self.delta
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct SnapshotDiff {
+    #[serde(serialize_with = "utils::serialize_path")]
+    old_root: PathBuf,
+    #[serde(serialize_with = "utils::serialize_path")]
+    new_root: PathBuf,
+    old_total_size: u64,
+    new_total_size: u64,
+    added: Vec<FileChange>,
+    removed: Vec<FileChange>,
+    modified: Vec<FileChange>,
+    grown: Vec<FileChange>,
+    folder_deltas: Vec<FolderDelta>,
+}
+
+impl SnapshotDiff {
+    pub fn old_root(&self) -> &PathBuf {
+        &self.old_root
+    }
+    pub fn new_root(&self) -> &PathBuf {
+        &self.new_root
+    }
+    /// Whether the snapshots were taken of different directories; paths are
+    /// still matched relative to each root, as for a moved or copied tree.
+    pub fn roots_differ(&self) -> bool {
+        self.old_root != self.new_root
+    }
+    pub fn old_total_size(&self) -> &u64 {
+        &self.old_total_size
+    }
+    pub fn new_total_size(&self) -> &u64 {
+        &self.new_total_size
+    }
+    pub fn added(&self) -> &Vec<FileChange> {
+        &self.added
+    }
+    pub fn removed(&self) -> &Vec<FileChange> {
+        &self.removed
+    }
+    pub fn modified(&self) -> &Vec<FileChange> {
+        &self.modified
+    }
+    pub fn grown(&self) -> &Vec<FileChange> {
+        &self.grown
+    }
+    pub fn folder_deltas(&self) -> &Vec<FolderDelta> {
+        &self.folder_deltas
+    }
+
+    pub(crate) fn set_roots(&mut self, old_root: PathBuf, new_root: PathBuf) {
+        self.old_root = old_root;
+        self.new_root = new_root;
+    }
+    pub(crate) fn set_total_sizes(&mut self, old_total_size: u64, new_total_size: u64) {
+        self.old_total_size = old_total_size;
+        self.new_total_size = new_total_size;
+    }
+    pub(crate) fn push_added(&mut self, change: FileChange) {
+        self.added.push(change);
+    }
+    pub(crate) fn push_removed(&mut self, change: FileChange) {
+        self.removed.push(change);
+    }
+    pub(crate) fn push_modified(&mut self, change: FileChange) {
+        self.modified.push(change);
+    }
+    pub(crate) fn push_grown(&mut self, change: FileChange) {
+        self.grown.push(change);
+    }
+    pub(crate) fn set_folder_deltas(&mut self, folder_deltas: Vec<FolderDelta>) {
+        self.folder_deltas = folder_deltas;
+    }
+
+    pub(crate) fn sort(&mut self) {
+        self.added.sort_by(|a, b| a.path.cmp(&b.path));
+        self.removed.sort_by(|a, b| a.path.cmp(&b.path));
+        self.modified.sort_by(|a, b| a.path.cmp(&b.path));
+        self.grown
+            .sort_by(|a, b| b.delta.cmp(&a.delta).then_with(|| a.path.cmp(&b.path)));
+        self.folder_deltas.sort_by(|a, b| {
+            b.delta
+                .abs()
+                .cmp(&a.delta.abs())
+                .then_with(|| a.path.cmp(&b.path))
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_directories_above_the_root_do_not_count() {
+        let root = Path::new("/home/u/build/proj");
+        assert_eq!(
+            FileCategory::of(root, &root.join("src/main.rs")),
+            FileCategory::Source
+        );
+        assert_eq!(
+            FileCategory::of(root, &root.join("target/debug/app")),
+            FileCategory::Build
+        );
+        assert_eq!(
+            FileCategory::of(root, &root.join("node_modules/x/index.js")),
+            FileCategory::Build
+        );
+    }
+
+    #[test]
+    fn rotated_logs_are_logs() {
+        let root = Path::new("/var/log");
+        assert_eq!(
+            FileCategory::of(root, &root.join("syslog.log.1")),
+            FileCategory::Log
+        );
+    }
+
+    #[test]
+    fn analyze_builder_rejects_conflicts() {
+        let builder = || AnalyzeOptions::builder(PathBuf::from("."));
+        assert_eq!(builder().with_top_n(0).build().err(), Some(OptionsError::ZeroTopN));
+        assert_eq!(
+            builder().with_snapshot(true).build().err(),
+            Some(OptionsError::MissingHashAlgorithm)
+        );
+        assert_eq!(
+            builder().with_inventory(true).build().err(),
+            Some(OptionsError::MissingHashAlgorithm)
+        );
+        assert!(matches!(
+            builder().with_ignore_patterns(vec!["[".to_string()]).build(),
+            Err(OptionsError::InvalidPattern { .. })
+        ));
+        assert_eq!(
+            builder().with_min_size(Some("1XB".to_string())).build().err(),
+            Some(OptionsError::InvalidSize("1XB".to_string()))
+        );
+
+        // Duplicates alone fall back to SHA-256
+        let options = builder().with_duplicates(true).build().unwrap();
+        assert_eq!(options.hash_algorithm(), HashAlgorithm::Sha256);
+        let options = builder()
+            .with_snapshot(true)
+            .with_hash_algorithm(HashAlgorithm::Blake3)
+            .build()
+            .unwrap();
+        assert_eq!(options.hash_algorithm(), HashAlgorithm::Blake3);
+    }
+
+    #[test]
+    fn diff_builder_compiles_ignore_patterns_once() {
+        let options = DiffOptions::builder(PathBuf::from("old.json"), PathBuf::from("."))
+            .with_ignore_patterns(vec!["*.tmp".to_string()])
+            .build()
+            .unwrap();
+        assert!(options.ignore_patterns()[0].matches("a.tmp"));
+        assert!(matches!(
+            DiffOptions::builder(PathBuf::from("old.json"), PathBuf::from("."))
+                .with_ignore_patterns(vec!["[".to_string()])
+                .build(),
+            Err(OptionsError::InvalidPattern { .. })
+        ));
+    }
+}
@@ -0,0 +1,36 @@
+use std::{collections::HashMap, fs};
+
+const PASSWD_PATH: &str = "/etc/passwd";
+const GROUP_PATH: &str = "/etc/group";
+
+/// User names by uid, from `/etc/passwd`; empty where it can't be read.
+pub fn user_names() -> HashMap<u32, String> {
+    read_id_names(PASSWD_PATH)
+}
+
+/// Group names by gid, from `/etc/group`; empty where it can't be read.
+pub fn group_names() -> HashMap<u32, String> {
+    read_id_names(GROUP_PATH)
+}
+
+// Both files are `name:password:id:...`, one entry per line
+fn read_id_names(path: &str) -> HashMap<u32, String> {
+    let Ok(content) = fs::read_to_string(path) else {
+        return HashMap::new();
+    };
+    let mut names = HashMap::new();
+    for line in content.lines() {
+        if line.starts_with('#') {
+            continue;
+        }
+        let mut fields = line.split(':');
+        let (Some(name), Some(_), Some(id)) = (fields.next(), fields.next(), fields.next()) else {
+            continue;
+        };
+        if let Ok(id) = id.parse() {
+            // The first entry wins, like getpwuid
+            names.entry(id).or_insert_with(|| name.to_string());
+        }
+    }
+    names
+}
@@ -0,0 +1,137 @@
+use std::{
+    path::Path,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use crate::model::{Progress, ProgressCallback};
+
+// Minimum time between two updates of the same phase
+const INTERVAL: Duration = Duration::from_millis(200);
+
+/// Counts scanned entries on the aggregating thread and reports them
+/// through the callback, if any, at most every `INTERVAL`.
+pub struct ScanProgress<'a> {
+    callback: Option<&'a ProgressCallback>,
+    started: Instant,
+    last_report: Instant,
+    entries: u64,
+    bytes: u64,
+    matches: u64,
+}
+
+impl<'a> ScanProgress<'a> {
+    pub fn new(callback: Option<&'a ProgressCallback>) -> Self {
+        let now = Instant::now();
+        ScanProgress {
+            callback,
+            started: now,
+            last_report: now,
+            entries: 0,
+            bytes: 0,
+            matches: 0,
+        }
+    }
+
+    /// Counts one entry of `bytes` at `path`.
+    pub fn add(&mut self, path: &Path, bytes: u64, matched: bool) {
+        let Some(callback) = self.callback else {
+            return;
+        };
+        self.entries += 1;
+        self.bytes += bytes;
+        if matched {
+            self.matches += 1;
+        }
+        if self.last_report.elapsed() >= INTERVAL {
+            self.last_report = Instant::now();
+            callback(&self.progress(path.parent()));
+        }
+    }
+
+    /// Reports the final counts.
+    pub fn finish(&self) {
+        if let Some(callback) = self.callback {
+            callback(&self.progress(None));
+        }
+    }
+
+    fn progress(&self, current_dir: Option<&Path>) -> Progress {
+        Progress::scanning(
+            self.entries,
+            self.bytes,
+            self.matches,
+            current_dir.map(Path::to_path_buf),
+            self.started.elapsed(),
+        )
+    }
+}
+
+/// Counts hashed files from any number of hash workers and reports them
+/// through the callback, if any, at most every `INTERVAL`.
+pub struct HashProgress<'a> {
+    callback: Option<&'a ProgressCallback>,
+    started: Instant,
+    files_to_hash: u64,
+    bytes_to_hash: u64,
+    files_hashed: AtomicU64,
+    bytes_hashed: AtomicU64,
+    // Milliseconds after `started`, so workers can claim a report slot
+    last_report: AtomicU64,
+}
+
+impl<'a> HashProgress<'a> {
+    pub fn new(
+        callback: Option<&'a ProgressCallback>,
+        files_to_hash: u64,
+        bytes_to_hash: u64,
+    ) -> Self {
+        HashProgress {
+            callback,
+            started: Instant::now(),
+            files_to_hash,
+            bytes_to_hash,
+            files_hashed: AtomicU64::new(0),
+            bytes_hashed: AtomicU64::new(0),
+            last_report: AtomicU64::new(0),
+        }
+    }
+
+    /// Counts one hashed file of `bytes`.
+    pub fn add(&self, bytes: u64) {
+        let Some(callback) = self.callback else {
+            return;
+        };
+        self.files_hashed.fetch_add(1, Ordering::Relaxed);
+        self.bytes_hashed.fetch_add(bytes, Ordering::Relaxed);
+
+        let now = self.started.elapsed().as_millis() as u64;
+        let last = self.last_report.load(Ordering::Relaxed);
+        // Only the worker that moves `last_report` forward reports
+        if now >= last + INTERVAL.as_millis() as u64
+            && self
+                .last_report
+                .compare_exchange(last, now, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+        {
+            callback(&self.progress());
+        }
+    }
+
+    /// Reports the final counts.
+    pub fn finish(&self) {
+        if let Some(callback) = self.callback {
+            callback(&self.progress());
+        }
+    }
+
+    fn progress(&self) -> Progress {
+        Progress::hashing(
+            self.files_hashed.load(Ordering::Relaxed),
+            self.files_to_hash,
+            self.bytes_hashed.load(Ordering::Relaxed),
+            self.bytes_to_hash,
+            self.started.elapsed(),
+        )
+    }
+}
@@ -0,0 +1,288 @@
+use std::{
+    fmt::Write as _,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use serde::Serialize;
+
+use crate::error::AnalysisError;
+use crate::model::{AnalyzeOptions, DirectorySummary, FileInfoDirectory, FolderInfo};
+use crate::tree::{NodeKind, SizeTree, SortKey};
+use crate::utils;
+
+const TEMPLATE: &str = include_str!("report.html");
+
+// Keeps the embedded tree small on huge scans: children below this share of
+// the total, or past the first MAX_CHILDREN, are folded into "(others)"
+const MIN_SHARE: f64 = 0.0005;
+const MAX_CHILDREN: usize = 200;
+
+#[derive(Serialize)]
+struct TreemapNode {
+    name: String,
+    size: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    children: Option<Vec<TreemapNode>>,
+}
+
+/// Writes a self-contained HTML report: summary, zoomable treemap of the
+/// size tree, largest files and folders, and duplicate groups.
+pub fn save_html_report(
+    option: &AnalyzeOptions,
+    summary: &DirectorySummary,
+    path: &Path,
+) -> Result<(), AnalysisError> {
+    let data = match summary.size_tree() {
+        Some(tree) => {
+            let total = (*tree.node(tree.root()).size()).max(1) as f64;
+            serde_json::to_string(&treemap_node(tree, tree.root(), total))
+                .map_err(|e| AnalysisError::IoError(e.into()))?
+        }
+        None => "{\"name\":\"\",\"size\":0}".to_string(),
+    };
+    let title = escape_html(&option.path().display().to_string());
+    let mut generated = format!(
+        "Generated {} &middot; scan took {:.1}s",
+        chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
+        summary.duration().as_secs_f64()
+    );
+    if let Some(reason) = summary.incomplete() {
+        let _ = write!(
+            generated,
+            " &middot; <strong>incomplete: scan {}</strong>",
+            reason.describe()
+        );
+    }
+    // A "</script>" inside a path would end the data block early
+    let data = data.replace("</", "<\\/");
+    let html = fill_template(TEMPLATE, |name| match name {
+        "TITLE" => Some(title.clone()),
+        "GENERATED" => Some(generated.clone()),
+        "SUMMARY" => Some(summary_list(summary)),
+        "FILES" => Some(files_table(summary.largest_files())),
+        "FOLDERS" => Some(folders_table(summary.largest_folders())),
+        "DUPLICATES" => Some(duplicates_section(summary)),
+        "DATA" => Some(data.clone()),
+        _ => None,
+    });
+
+    let file = File::create(path).map_err(AnalysisError::IoError)?;
+    let mut writer = BufWriter::new(file);
+    writer
+        .write_all(html.as_bytes())
+        .map_err(AnalysisError::IoError)?;
+    writer.flush().map_err(AnalysisError::IoError)
+}
+
+// Replaces each `{{NAME}}` in `template` with `value(NAME)` in a single pass,
+// so placeholders inside inserted text (e.g. a file named "{{DATA}}") stay
+// as they are; unknown names are kept verbatim
+fn fill_template(template: &str, value: impl Fn(&str) -> Option<String>) -> String {
+    let mut html = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        html.push_str(&rest[..start]);
+        let token = &rest[start..];
+        match token.find("}}").and_then(|end| Some((end, value(&token[2..end])?))) {
+            Some((end, text)) => {
+                html.push_str(&text);
+                rest = &token[end + 2..];
+            }
+            None => {
+                html.push_str("{{");
+                rest = &token[2..];
+            }
+        }
+    }
+    html.push_str(rest);
+    html
+}
+
+fn treemap_node(tree: &SizeTree, id: usize, total: f64) -> TreemapNode {
+    let node = tree.node(id);
+    let name = if id == tree.root() {
+        node.path().display().to_string()
+    } else {
+        node.name()
+    };
+    if node.kind() == NodeKind::File {
+        return TreemapNode {
+            name,
+            size: *node.size(),
+            children: None,
+        };
+    }
+
+    let sorted = tree.sorted_children(id, SortKey::Size);
+    let kept = sorted
+        .iter()
+        .take(MAX_CHILDREN)
+        .take_while(|child| *tree.node(**child).size() as f64 / total >= MIN_SHARE)
+        .count();
+    let mut children: Vec<TreemapNode> = sorted[..kept]
+        .iter()
+        .map(|child| treemap_node(tree, *child, total))
+        .collect();
+    let others: u64 = sorted[kept..]
+        .iter()
+        .map(|child| *tree.node(*child).size())
+        .sum();
+    if others > 0 {
+        children.push(TreemapNode {
+            name: format!("({} others)", sorted.len() - kept),
+            size: others,
+            children: None,
+        });
+    }
+    TreemapNode {
+        name,
+        size: *node.size(),
+        children: Some(children),
+    }
+}
+
+fn summary_list(summary: &DirectorySummary) -> String {
+    let rows = [
+        ("Total size", utils::format_size(summary.total_size())),
+        ("Disk usage", utils::format_size(summary.disk_usage())),
+        ("Files", utils::format_number(summary.file_count())),
+        ("Folders", utils::format_number(summary.folder_count())),
+        ("Symlinks", utils::format_number(summary.symlink_count())),
+    ];
+    let mut html = String::from("<dl>\n");
+    for (label, value) in rows {
+        let _ = writeln!(html, "<dt>{}</dt><dd>{}</dd>", label, value);
+    }
+    html.push_str("</dl>");
+    html
+}
+
+fn files_table(files: &[FileInfoDirectory]) -> String {
+    let rows = files
+        .iter()
+        .map(|file| (file.path().display().to_string(), file.size(), file.disk_usage()));
+    usage_table(rows)
+}
+
+fn folders_table(folders: &[FolderInfo]) -> String {
+    let rows = folders
+        .iter()
+        .map(|folder| (folder.path().display().to_string(), folder.size(), folder.disk_usage()));
+    usage_table(rows)
+}
+
+fn usage_table<'a>(rows: impl Iterator<Item = (String, &'a u64, &'a u64)>) -> String {
+    let mut html = String::from(
+        "<table>\n<tr><th>#</th><th>Path</th><th class=\"num\">Size</th>\
+         <th class=\"num\">On disk</th></tr>\n",
+    );
+    for (idx, (path, size, disk_usage)) in rows.enumerate() {
+        let _ = writeln!(
+            html,
+            "<tr><td>{}</td><td>{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td></tr>",
+            idx + 1,
+            escape_html(&path),
+            utils::format_size(size),
+            utils::format_size(disk_usage)
+        );
+    }
+    html.push_str("</table>");
+    html
+}
+
+fn duplicates_section(summary: &DirectorySummary) -> String {
+    let Some(duplicates) = summary.duplicates() else {
+        return String::new();
+    };
+    let mut html = String::from("<h2>Duplicates</h2>\n");
+    if duplicates.is_empty() {
+        html.push_str("<p>No duplicate files found.</p>");
+        return html;
+    }
+    html.push_str(
+        "<table>\n<tr><th>#</th><th class=\"num\">Size</th><th>Hash</th><th>Files</th></tr>\n",
+    );
+    for (idx, group) in duplicates.iter().enumerate() {
+        let mut files: Vec<String> = group
+            .files()
+            .iter()
+            .map(|file| escape_html(&file.display().to_string()))
+            .collect();
+        files.extend(group.hard_links().iter().map(|link| {
+            format!(
+                "{} (hard link)",
+                escape_html(&link.path().display().to_string())
+            )
+        }));
+        let _ = writeln!(
+            html,
+            "<tr><td>{}</td><td class=\"num\">{}</td><td><code>{}</code></td><td>{}</td></tr>",
+            idx + 1,
+            utils::format_size(group.size()),
+            escape_html(group.hash()),
+            files.join("<br>")
+        );
+    }
+    html.push_str("</table>");
+    html
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::directory_analyzer;
+
+    #[test]
+    fn fill_template_does_not_rescan_inserted_text() {
+        let html = fill_template("<h1>{{TITLE}}</h1>{{DATA}}{{UNKNOWN}}", |name| match name {
+            "TITLE" => Some("{{DATA}}".to_string()),
+            "DATA" => Some("{\"name\":\"x\"}".to_string()),
+            _ => None,
+        });
+        assert_eq!(html, "<h1>{{DATA}}</h1>{\"name\":\"x\"}{{UNKNOWN}}");
+    }
+
+    #[test]
+    fn placeholder_in_path_does_not_inject_data() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path().join("scan");
+        let folder = root.join("{{DATA}}");
+        fs::create_dir_all(&folder).unwrap();
+        fs::write(folder.join("<img src=x onerror=alert(1)>"), b"payload").unwrap();
+
+        let options = AnalyzeOptions::builder(root.clone())
+            .with_size_tree()
+            .build()
+            .unwrap();
+        let summary = directory_analyzer(&options).unwrap();
+        let report = temp.path().join("report.html");
+        save_html_report(&options, &summary, &report).unwrap();
+        let html = fs::read_to_string(&report).unwrap();
+
+        // Raw names may only appear inside the JSON data block
+        let data_start = html.find("<script type=\"application/json\"").unwrap();
+        assert_eq!(html.matches("<script type=\"application/json\"").count(), 1);
+        assert!(!html[..data_start].contains("<img"));
+        assert!(html[..data_start].contains("{{DATA}}"));
+        assert!(html[..data_start].contains("&lt;img src=x onerror=alert(1)&gt;"));
+    }
+}
@@ -1,16 +1,41 @@
-use std::time::Instant;
+use std::path::{Path, PathBuf};
+use std::time::{Instant, UNIX_EPOCH};
 
-use walkdir::{DirEntry, WalkDir};
+use ignore::WalkState;
 
 use crate::error::AnalysisError;
-use crate::model::{FileInfoSearch, SearchOptions, SearchResult};
+use crate::model::{ContentMatch, FileInfoSearch, FileType, MountPoint, SearchOptions, SearchResult};
+use crate::progress::ScanProgress;
+use crate::walker;
 
+/// Walks `option.path()` and returns every entry matching all of the
+/// configured criteria, sorted by path.
 pub fn file_finder(option: &SearchOptions) -> Result<SearchResult, AnalysisError> {
+    let mut file_result: Vec<FileInfoSearch> = Vec::new();
+    let mut search_result = stream_files(option, |file| file_result.push(file))?;
+    file_result.sort_by(|a, b| a.path().cmp(b.path()));
+    search_result.set_files_result(file_result);
+    Ok(search_result)
+}
+
+/// Like [`file_finder`], but hands each match to `on_match` as soon as it is
+/// found instead of keeping it, so memory stays flat on huge trees.
+///
+/// Matches arrive in walk order, which varies between runs. `on_match` runs
+/// on the calling thread. The returned result has the counts, total size and
+/// mount points, and an empty list of files.
+pub fn stream_files<F>(
+    option: &SearchOptions,
+    mut on_match: F,
+) -> Result<SearchResult, AnalysisError>
+where
+    F: FnMut(FileInfoSearch),
+{
     let start_time: Instant = Instant::now();
     let path = option
         .path()
         .canonicalize()
-        .map_err(|e| AnalysisError::IoError(e))?;
+        .map_err(AnalysisError::IoError)?;
 
     if !path.exists() {
         return Err(AnalysisError::PathNotFound(path));
@@ -18,13 +43,17 @@ pub fn file_finder(option: &SearchOptions) -> Result<SearchResult, AnalysisError
 
     let mut search_result: SearchResult =
         SearchResult::new(0, 0, std::time::Duration::default(), Vec::new());
-    let mut file_result: Vec<FileInfoSearch> = Vec::new();
 
-    for entry in WalkDir::new(&path)
-        .follow_links(false)
-        .into_iter()
-        .filter_map(handle_dir_entry)
-    {
+    let builder = walker::walk_builder(
+        &path,
+        None,
+        false,
+        option.respect_gitignore(),
+        option.threads(),
+    );
+    // Matching (including content search) runs on the walker threads; every
+    // entry of a wanted type is emitted so the aggregator can count it
+    let visit = |entry: ignore::DirEntry, emitter: &walker::Emitter<Searched>| {
         let metadata = match entry.metadata() {
             Ok(m) => m,
             Err(e) => {
@@ -33,50 +62,136 @@ pub fn file_finder(option: &SearchOptions) -> Result<SearchResult, AnalysisError
                     entry.path().display(),
                     e
                 );
-                continue;
+                return WalkState::Continue;
             }
         };
 
-        if metadata.is_file() {
-            search_result.increment_file_searched();
-
-            if !option.match_name_pattern(entry.path()) {
-                continue;
+        if walker::is_mount_point(&entry, &metadata) {
+            let included = !option.one_file_system();
+            emitter.emit(Searched::MountPoint(MountPoint::new(
+                entry.path().to_path_buf(),
+                included,
+            )));
+            if !included {
+                return WalkState::Skip;
             }
-            let (matched, content) = option.match_content_pattern(entry.path());
-            if !matched {
-                continue;
+        }
+        // A directory given as the search root is never a result itself; a
+        // file given as the root is searched like any other
+        if entry.depth() == 0 && metadata.is_dir() {
+            return WalkState::Continue;
+        }
+        if let Some(file_type) = FileType::from_metadata(&metadata) {
+            if option.match_file_type(file_type) {
+                emitter.emit(match_file(option, &path, entry, file_type, &metadata));
             }
-            let modified_time = metadata.modified().unwrap();
-            if !option.match_modified_date(&modified_time) {
-                continue;
+        }
+        WalkState::Continue
+    };
+
+    let mut mount_points = Vec::new();
+    let mut progress = ScanProgress::new(option.progress());
+    walker::walk_parallel(&builder, visit, |searched| {
+        if let Searched::MountPoint(mount_point) = searched {
+            mount_points.push(mount_point);
+            return;
+        }
+        search_result.increment_file_searched();
+        match searched {
+            Searched::Matched(file) => {
+                progress.add(file.path(), *file.size(), true);
+                search_result.add_to_total_size(*file.size());
+                search_result.increment_files_found();
+                on_match(file);
             }
-            let size = metadata.len();
-            if !option.match_size(&size) {
-                continue;
+            Searched::SkippedBinary(path, size) => {
+                progress.add(&path, size, false);
+                search_result.increment_binary_skipped();
             }
-
-            file_result.push(FileInfoSearch::new(
-                entry.path().to_path_buf(),
-                size,
-                content,
-                modified_time,
-            ));
-            search_result.add_to_total_size(size);
+            Searched::Missed(path, size) => progress.add(&path, size, false),
+            Searched::MountPoint(_) => {}
         }
-    }
-    search_result.set_files_result(file_result);
+    });
+    progress.finish();
+
+    mount_points.sort_by(|a, b| a.path().cmp(b.path()));
+    search_result.set_mount_points(mount_points);
     search_result.set_duration(start_time.elapsed());
 
     Ok(search_result)
 }
 
-fn handle_dir_entry(entry: Result<DirEntry, walkdir::Error>) -> Option<DirEntry> {
-    match entry {
-        Ok(entry) => Some(entry),
-        Err(e) => {
-            eprintln!("Warning: {}", e);
-            None
-        }
+// Entries that didn't match keep their path and size for progress reports
+enum Searched {
+    Matched(FileInfoSearch),
+    Missed(PathBuf, u64),
+    SkippedBinary(PathBuf, u64),
+    MountPoint(MountPoint),
+}
+
+fn match_file(
+    option: &SearchOptions,
+    root: &Path,
+    entry: ignore::DirEntry,
+    file_type: FileType,
+    metadata: &std::fs::Metadata,
+) -> Searched {
+    let size = metadata.len();
+    if !option.match_name_pattern(entry.path()) {
+        return Searched::Missed(entry.into_path(), size);
+    }
+    // A file without an mtime (unsupported by some filesystems) can't meet a
+    // date bound, and is listed as modified at the epoch otherwise
+    let dated = option.modified_after().is_some() || option.modified_before().is_some();
+    let modified_time = match metadata.modified() {
+        Ok(time) if option.match_modified_date(&time) => time,
+        Err(_) if !dated => UNIX_EPOCH,
+        _ => return Searched::Missed(entry.into_path(), size),
+    };
+    if !option.match_size(&size) {
+        return Searched::Missed(entry.into_path(), size);
+    }
+    if !option.match_filter(root, entry.path(), metadata) {
+        return Searched::Missed(entry.into_path(), size);
+    }
+
+    // Content is checked last since it means reading the file; only regular
+    // files have any (opening a fifo would block)
+    if option.content_pattern().is_some() && file_type != FileType::File {
+        return Searched::Missed(entry.into_path(), size);
+    }
+    let (matches, binary_offsets) = match option.match_content_pattern(entry.path()) {
+        ContentMatch::Unfiltered => (Vec::new(), None),
+        ContentMatch::Lines(matches) => (matches, None),
+        ContentMatch::Binary(offsets) => (Vec::new(), Some(offsets)),
+        ContentMatch::SkippedBinary => return Searched::SkippedBinary(entry.into_path(), size),
+        ContentMatch::NoMatch => return Searched::Missed(entry.into_path(), size),
+    };
+
+    let mut file = FileInfoSearch::new(entry.into_path(), file_type, size, matches, modified_time);
+    if let Some(offsets) = binary_offsets {
+        file.set_binary_offsets(offsets);
+    }
+    Searched::Matched(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn file_root_is_searched() {
+        let temp = tempfile::tempdir().unwrap();
+        let file = temp.path().join("notes.txt");
+        fs::write(&file, "foo bar\n").unwrap();
+        let options = SearchOptions::builder(file.clone())
+            .with_content_pattern(Some("foo".to_string()))
+            .build()
+            .unwrap();
+        let result = file_finder(&options).unwrap();
+        assert_eq!(*result.file_searched(), 1);
+        assert_eq!(result.files_result().len(), 1);
     }
 }
@@ -0,0 +1,249 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::analyzer::{add_to_folder_sizes, directory_analyzer};
+use crate::error::AnalysisError;
+use crate::model::{
+    AnalyzeOptions, DiffOptions, FileChange, FolderDelta, Snapshot, SnapshotDiff, SnapshotEntry,
+    SNAPSHOT_VERSION,
+};
+
+pub fn save_snapshot(snapshot: &Snapshot, path: &Path) -> Result<(), AnalysisError> {
+    let file = File::create(path).map_err(AnalysisError::IoError)?;
+    let mut writer = BufWriter::new(file);
+    serde_json::to_writer(&mut writer, snapshot)
+        .map_err(|e| AnalysisError::InvalidSnapshot(path.to_path_buf(), e.to_string()))?;
+    writer.flush().map_err(AnalysisError::IoError)
+}
+
+pub fn load_snapshot(path: &Path) -> Result<Snapshot, AnalysisError> {
+    let file = File::open(path).map_err(AnalysisError::IoError)?;
+    let snapshot: Snapshot = serde_json::from_reader(BufReader::new(file))
+        .map_err(|e| AnalysisError::InvalidSnapshot(path.to_path_buf(), e.to_string()))?;
+
+    if snapshot.version() != SNAPSHOT_VERSION {
+        return Err(AnalysisError::InvalidSnapshot(
+            path.to_path_buf(),
+            format!("unsupported snapshot version {}", snapshot.version()),
+        ));
+    }
+    Ok(snapshot)
+}
+
+/// Compares the `old` snapshot against `new`, which is either another
+/// snapshot file or a directory to scan live.
+pub fn snapshot_diff(option: &DiffOptions) -> Result<SnapshotDiff, AnalysisError> {
+    let old = load_snapshot(option.old())?;
+    let new = if option.new_path().is_dir() {
+        capture_snapshot(option, &old)?
+    } else {
+        load_snapshot(option.new_path())?
+    };
+
+    Ok(diff_snapshots(&old, &new))
+}
+
+fn capture_snapshot(option: &DiffOptions, old: &Snapshot) -> Result<Snapshot, AnalysisError> {
+    let builder = AnalyzeOptions::builder(option.new_path().clone())
+        .with_top_n(1)
+        .with_compiled_ignore_patterns(option.ignore_patterns().clone())
+        .with_threads(option.threads());
+
+    // Only pay for hashing when the old side can be compared by content
+    let builder = match old.hash_algorithm() {
+        Some(algorithm) => builder.with_hash_algorithm(algorithm).with_snapshot(true),
+        None => builder.with_snapshot(false),
+    };
+    let builder = match option.cancel_token() {
+        Some(token) => builder.with_cancel_token(token.clone()),
+        None => builder,
+    };
+    let analyze_options = builder.build().map_err(AnalysisError::InvalidOptions)?;
+
+    let mut summary = directory_analyzer(&analyze_options)?;
+    // Files the scan never reached would show up as removed
+    if let Some(reason) = summary.incomplete() {
+        return Err(AnalysisError::Interrupted(reason));
+    }
+    Ok(summary
+        .take_snapshot()
+        .unwrap_or_else(|| Snapshot::new(option.new_path().clone(), None)))
+}
+
+pub fn diff_snapshots(old: &Snapshot, new: &Snapshot) -> SnapshotDiff {
+    let mut diff = SnapshotDiff::default();
+    diff.set_roots(old.root().clone(), new.root().clone());
+    let old_files: HashMap<&PathBuf, &SnapshotEntry> =
+        old.files().iter().map(|f| (f.path(), f)).collect();
+    let new_paths: HashSet<&PathBuf> = new.files().iter().map(|f| f.path()).collect();
+
+    for file in new.files() {
+        match old_files.get(file.path()) {
+            None => diff.push_added(FileChange::new(
+                file.path().clone(),
+                None,
+                Some(*file.size()),
+            )),
+            Some(previous) if is_modified(previous, file) => {
+                let change =
+                    FileChange::new(file.path().clone(), Some(*previous.size()), Some(*file.size()));
+                if file.size() > previous.size() {
+                    diff.push_grown(change.clone());
+                }
+                diff.push_modified(change);
+            }
+            Some(_) => {}
+        }
+    }
+    for file in old.files() {
+        if !new_paths.contains(file.path()) {
+            diff.push_removed(FileChange::new(file.path().clone(), Some(*file.size()), None));
+        }
+    }
+
+    let old_folders = folder_sizes(old);
+    let new_folders = folder_sizes(new);
+    let folders: HashSet<&PathBuf> = old_folders.keys().chain(new_folders.keys()).collect();
+    let folder_deltas = folders
+        .into_iter()
+        .map(|folder| {
+            FolderDelta::new(
+                folder.clone(),
+                old_folders.get(folder).copied().unwrap_or_default(),
+                new_folders.get(folder).copied().unwrap_or_default(),
+            )
+        })
+        .filter(|delta| delta.delta() != 0)
+        .collect();
+
+    diff.set_folder_deltas(folder_deltas);
+    diff.set_total_sizes(
+        old.files().iter().map(|f| f.size()).sum(),
+        new.files().iter().map(|f| f.size()).sum(),
+    );
+    diff.sort();
+    diff
+}
+
+fn is_modified(old: &SnapshotEntry, new: &SnapshotEntry) -> bool {
+    if old.size() != new.size() {
+        return true;
+    }
+    match (old.hash(), new.hash()) {
+        (Some(old_hash), Some(new_hash)) => old_hash != new_hash,
+        _ => old.modified() != new.modified(),
+    }
+}
+
+// Snapshot paths are relative, so the empty path stands for the root
+fn folder_sizes(snapshot: &Snapshot) -> HashMap<PathBuf, u64> {
+    let mut folder_sizes = HashMap::new();
+    for file in snapshot.files() {
+        add_to_folder_sizes(&mut folder_sizes, Path::new(""), file.path(), *file.size());
+    }
+    folder_sizes
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use super::*;
+    use crate::model::{CancelToken, StopReason};
+
+    fn snapshot(root: &str, files: &[(&str, u64, u64)]) -> Snapshot {
+        let mut snapshot = Snapshot::new(PathBuf::from(root), None);
+        for (path, size, modified) in files {
+            let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(*modified);
+            snapshot.push(SnapshotEntry::new(PathBuf::from(path), *size, modified));
+        }
+        snapshot
+    }
+
+    #[test]
+    fn cancelled_live_capture_is_an_error() {
+        let temp = tempfile::tempdir().unwrap();
+        let old = temp.path().join("old.json");
+        save_snapshot(&snapshot("/data", &[("a", 1, 1)]), &old).unwrap();
+        let cancel = CancelToken::new();
+        cancel.cancel();
+
+        let options = DiffOptions::builder(old, temp.path().to_path_buf())
+            .with_cancel_token(cancel)
+            .build()
+            .unwrap();
+        assert!(matches!(
+            snapshot_diff(&options),
+            Err(AnalysisError::Interrupted(StopReason::Cancelled))
+        ));
+    }
+
+    fn paths(changes: &[FileChange]) -> Vec<&str> {
+        changes.iter().map(|change| change.path().to_str().unwrap()).collect()
+    }
+
+    #[test]
+    fn reports_added_removed_grown_and_shrunk_files() {
+        let old = snapshot(
+            "/data",
+            &[
+                ("a/grown", 100, 1),
+                ("a/shrunk", 500, 1),
+                ("a/same", 10, 1),
+                ("a/touched", 10, 1),
+                ("b/removed", 40, 1),
+            ],
+        );
+        let new = snapshot(
+            "/data",
+            &[
+                ("a/grown", 300, 2),
+                ("a/shrunk", 200, 2),
+                ("a/same", 10, 1),
+                ("a/touched", 10, 2),
+                ("c/added", 70, 2),
+            ],
+        );
+        let diff = diff_snapshots(&old, &new);
+
+        assert!(!diff.roots_differ());
+        assert_eq!(paths(diff.added()), ["c/added"]);
+        assert_eq!(paths(diff.removed()), ["b/removed"]);
+        assert_eq!(paths(diff.modified()), ["a/grown", "a/shrunk", "a/touched"]);
+        assert_eq!(paths(diff.grown()), ["a/grown"]);
+        assert_eq!((*diff.old_total_size(), *diff.new_total_size()), (660, 590));
+
+        // Largest change first; a/ lost 100 bytes net, the root 70
+        let deltas: Vec<(&str, i64)> = diff
+            .folder_deltas()
+            .iter()
+            .map(|delta| (delta.path().to_str().unwrap(), delta.delta()))
+            .collect();
+        assert_eq!(deltas, [("a", -100), ("", -70), ("c", 70), ("b", -40)]);
+    }
+
+    #[test]
+    fn hashes_decide_over_modification_times() {
+        let mut old = snapshot("/data", &[("same", 10, 1), ("edited", 10, 1)]);
+        let mut new = snapshot("/data", &[("same", 10, 2), ("edited", 10, 1)]);
+        for (snapshot, edited_hash) in [(&mut old, "1"), (&mut new, "2")] {
+            let files = snapshot.files_mut();
+            files[0].set_hash(Some("0".to_string()));
+            files[1].set_hash(Some(edited_hash.to_string()));
+        }
+        assert_eq!(paths(diff_snapshots(&old, &new).modified()), ["edited"]);
+    }
+
+    #[test]
+    fn different_roots_are_flagged() {
+        let old = snapshot("/data", &[("file", 10, 1)]);
+        let new = snapshot("/backup/data", &[("file", 10, 1)]);
+        let diff = diff_snapshots(&old, &new);
+        assert!(diff.roots_differ());
+        assert!(diff.modified().is_empty() && diff.added().is_empty());
+    }
+}
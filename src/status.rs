@@ -0,0 +1,113 @@
+use std::io::{self, IsTerminal, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use ratatui::crossterm::terminal;
+
+use fs_tool::model::{Progress, ProgressPhase};
+use fs_tool::utils;
+
+const CLEAR_LINE: &str = "\r\x1b[2K";
+
+/// A single self-overwriting progress line on stderr.
+pub struct StatusLine {
+    // What the scan phase is called: "Scanning" or "Searching"
+    verb: &'static str,
+    // Whether a line is currently drawn; also serializes writes
+    drawn: Mutex<bool>,
+}
+
+impl StatusLine {
+    /// A status line, unless it is disabled or stderr isn't a terminal.
+    pub fn stderr(verb: &'static str, enabled: bool) -> Option<Arc<StatusLine>> {
+        (enabled && io::stderr().is_terminal()).then(|| {
+            Arc::new(StatusLine {
+                verb,
+                drawn: Mutex::new(false),
+            })
+        })
+    }
+
+    /// A progress callback drawing into this line.
+    pub fn callback(self: &Arc<Self>) -> impl Fn(&Progress) + Send + Sync + 'static {
+        let status = Arc::clone(self);
+        move |progress| status.update(progress)
+    }
+
+    pub fn update(&self, progress: &Progress) {
+        let line = self.format(progress);
+        // Some terminals report a width of 0
+        let width = match terminal::size() {
+            Ok((columns, _)) if columns > 0 => columns as usize,
+            _ => 80,
+        };
+        let line: String = line.chars().take(width.saturating_sub(1)).collect();
+
+        let mut drawn = self.drawn.lock().unwrap_or_else(|e| e.into_inner());
+        let mut stderr = io::stderr().lock();
+        let _ = write!(stderr, "{}{}", CLEAR_LINE, line);
+        let _ = stderr.flush();
+        *drawn = true;
+    }
+
+    /// Erases the line, so regular output starts on a clean line.
+    pub fn clear(&self) {
+        let mut drawn = self.drawn.lock().unwrap_or_else(|e| e.into_inner());
+        if *drawn {
+            let mut stderr = io::stderr().lock();
+            let _ = write!(stderr, "{}", CLEAR_LINE);
+            let _ = stderr.flush();
+            *drawn = false;
+        }
+    }
+
+    fn format(&self, progress: &Progress) -> String {
+        let seconds = progress.elapsed().as_secs_f64().max(0.001);
+        match progress.phase() {
+            ProgressPhase::Hashing => {
+                let mut line = format!(
+                    "Hashing: {}/{} files, {} of {}, {}/s",
+                    utils::format_number(&progress.files_hashed()),
+                    utils::format_number(&progress.files_to_hash()),
+                    utils::format_size(&progress.bytes_hashed()),
+                    utils::format_size(&progress.bytes_to_hash()),
+                    utils::format_size(&((progress.bytes_hashed() as f64 / seconds) as u64)),
+                );
+                if let Some(eta) = progress.eta() {
+                    line.push_str(&format!(", ETA {}", format_eta(eta)));
+                }
+                line
+            }
+            _ => {
+                let mut line = format!(
+                    "{}: {} entries, {}, {}/s",
+                    self.verb,
+                    utils::format_number(&progress.entries()),
+                    utils::format_size(&progress.bytes()),
+                    utils::format_number(&((progress.entries() as f64 / seconds) as u64)),
+                );
+                if progress.matches() > 0 {
+                    line.push_str(&format!(
+                        ", {} matches",
+                        utils::format_number(&progress.matches())
+                    ));
+                }
+                if let Some(dir) = progress.current_dir() {
+                    line.push_str(&format!(" - {}", dir.display()));
+                }
+                line
+            }
+        }
+    }
+}
+
+fn format_eta(eta: Duration) -> String {
+    let seconds = eta.as_secs();
+    if seconds >= 3600 {
+        format!("{}h{:02}m", seconds / 3600, seconds % 3600 / 60)
+    } else if seconds >= 60 {
+        format!("{}m{:02}s", seconds / 60, seconds % 60)
+    } else {
+        format!("{}s", seconds)
+    }
+}
@@ -0,0 +1,223 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    Dir,
+    File,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SortKey {
+    Size,
+    Count,
+    Modified,
+    Name,
+}
+
+#[derive(Debug)]
+pub struct SizeNode {
+    path: PathBuf,
+    kind: NodeKind,
+    size: u64,
+    file_count: u64,
+    modified: Option<SystemTime>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+}
+
+impl SizeNode {
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    pub fn name(&self) -> String {
+        self.path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| self.path.display().to_string())
+    }
+
+    pub fn kind(&self) -> NodeKind {
+        self.kind
+    }
+
+    pub fn size(&self) -> &u64 {
+        &self.size
+    }
+
+    pub fn file_count(&self) -> &u64 {
+        &self.file_count
+    }
+
+    pub fn modified(&self) -> &Option<SystemTime> {
+        &self.modified
+    }
+
+    pub fn parent(&self) -> Option<usize> {
+        self.parent
+    }
+}
+
+/// Full hierarchy of sizes below the analyzed root.
+///
+/// Nodes live in an arena and refer to each other by index; directory sizes,
+/// file counts and modification times (latest below the directory) are kept
+/// up to date as files are added, detached or moved.
+#[derive(Debug)]
+pub struct SizeTree {
+    nodes: Vec<SizeNode>,
+    index: HashMap<PathBuf, usize>,
+}
+
+impl SizeTree {
+    pub fn new(root: PathBuf) -> Self {
+        let mut index = HashMap::new();
+        index.insert(root.clone(), 0);
+        SizeTree {
+            nodes: vec![SizeNode {
+                path: root,
+                kind: NodeKind::Dir,
+                size: 0,
+                file_count: 0,
+                modified: None,
+                parent: None,
+                children: Vec::new(),
+            }],
+            index,
+        }
+    }
+
+    pub fn root(&self) -> usize {
+        0
+    }
+
+    pub fn node(&self, id: usize) -> &SizeNode {
+        &self.nodes[id]
+    }
+
+    pub fn find(&self, path: &Path) -> Option<usize> {
+        self.index.get(path).copied()
+    }
+
+    pub fn add_dir(&mut self, path: &Path) -> Option<usize> {
+        self.ensure_dir(path)
+    }
+
+    pub fn add_file(&mut self, path: &Path, size: u64, modified: Option<SystemTime>) {
+        let Some(parent) = path.parent().and_then(|p| self.ensure_dir(p)) else {
+            return;
+        };
+        let id = self.push_node(path.to_path_buf(), NodeKind::File, Some(parent));
+        self.nodes[id].size = size;
+        self.nodes[id].file_count = 1;
+        self.nodes[id].modified = modified;
+        self.propagate(parent, size as i64, 1, modified);
+    }
+
+    pub fn sorted_children(&self, id: usize, key: SortKey) -> Vec<usize> {
+        let mut children = self.nodes[id].children.clone();
+        children.sort_by(|a, b| {
+            let (a, b) = (&self.nodes[*a], &self.nodes[*b]);
+            let order = match key {
+                SortKey::Size => b.size.cmp(&a.size),
+                SortKey::Count => b.file_count.cmp(&a.file_count),
+                SortKey::Modified => b.modified.cmp(&a.modified),
+                SortKey::Name => std::cmp::Ordering::Equal,
+            };
+            order.then_with(|| a.path.cmp(&b.path))
+        });
+        children
+    }
+
+    /// Removes a node and its subtree from the hierarchy.
+    pub fn detach(&mut self, id: usize) {
+        let Some(parent) = self.nodes[id].parent else {
+            return;
+        };
+        self.nodes[parent].children.retain(|child| *child != id);
+        self.nodes[id].parent = None;
+        let (size, count) = (self.nodes[id].size, self.nodes[id].file_count);
+        self.propagate(parent, -(size as i64), -(count as i64), None);
+        self.reindex(id, None);
+    }
+
+    /// Re-parents a detached node under `parent` at `path`.
+    pub fn attach(&mut self, id: usize, parent: usize, path: PathBuf) {
+        self.nodes[parent].children.push(id);
+        self.nodes[id].parent = Some(parent);
+        let (size, count, modified) = (
+            self.nodes[id].size,
+            self.nodes[id].file_count,
+            self.nodes[id].modified,
+        );
+        self.propagate(parent, size as i64, count as i64, modified);
+        self.reindex(id, Some(path));
+    }
+
+    fn ensure_dir(&mut self, path: &Path) -> Option<usize> {
+        if let Some(id) = self.index.get(path) {
+            return Some(*id);
+        }
+        // Anything outside the root has no place in the tree
+        if !path.starts_with(&self.nodes[0].path) {
+            return None;
+        }
+        let parent = self.ensure_dir(path.parent()?)?;
+        Some(self.push_node(path.to_path_buf(), NodeKind::Dir, Some(parent)))
+    }
+
+    fn push_node(&mut self, path: PathBuf, kind: NodeKind, parent: Option<usize>) -> usize {
+        let id = self.nodes.len();
+        self.index.insert(path.clone(), id);
+        self.nodes.push(SizeNode {
+            path,
+            kind,
+            size: 0,
+            file_count: 0,
+            modified: None,
+            parent,
+            children: Vec::new(),
+        });
+        if let Some(parent) = parent {
+            self.nodes[parent].children.push(id);
+        }
+        id
+    }
+
+    fn propagate(&mut self, from: usize, size: i64, count: i64, modified: Option<SystemTime>) {
+        let mut current = Some(from);
+        while let Some(id) = current {
+            let node = &mut self.nodes[id];
+            node.size = node.size.saturating_add_signed(size);
+            node.file_count = node.file_count.saturating_add_signed(count);
+            if modified > node.modified {
+                node.modified = modified;
+            }
+            current = node.parent;
+        }
+    }
+
+    // Moves the subtree under `path` in the index, or drops it when `None`
+    fn reindex(&mut self, id: usize, path: Option<PathBuf>) {
+        let old_path = self.nodes[id].path.clone();
+        self.index.remove(&old_path);
+        if let Some(path) = &path {
+            self.index.insert(path.clone(), id);
+            self.nodes[id].path = path.clone();
+        }
+        for child in self.nodes[id].children.clone() {
+            let child_path = path.as_ref().and_then(|parent| {
+                self.nodes[child]
+                    .path
+                    .file_name()
+                    .map(|name| parent.join(name))
+            });
+            self.reindex(child, child_path);
+        }
+    }
+}
@@ -0,0 +1,439 @@
+use std::{
+    collections::{BTreeSet, HashSet},
+    error::Error,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    layout::{Constraint, Layout},
+    style::{Color, Modifier, Style},
+    text::Line,
+    widgets::{Block, Borders, Cell, Paragraph, Row, Table, TableState},
+    DefaultTerminal, Frame,
+};
+
+use fs_tool::utils;
+use fs_tool::{NodeKind, SizeTree, SortKey};
+
+const BAR_WIDTH: usize = 10;
+const HELP: &str = "↑↓ move  →/⏎ open  ← up  s/c/m/n sort  ␣ mark  d delete  v move  q quit";
+
+enum Mode {
+    Browse,
+    ConfirmDelete(Vec<usize>, DeleteScope),
+    MoveTo(Vec<usize>, String),
+}
+
+// What deleting the targets removes on disk right now
+#[derive(Default)]
+struct DeleteScope {
+    files: u64,
+    folders: u64,
+    bytes: u64,
+    // (device, inode) of hard-linked files already counted in `bytes`
+    linked: HashSet<(u64, u64)>,
+}
+
+struct Browser {
+    tree: SizeTree,
+    duplicates: HashSet<PathBuf>,
+    current: usize,
+    entries: Vec<usize>,
+    state: TableState,
+    sort: SortKey,
+    marked: BTreeSet<usize>,
+    mode: Mode,
+    status: String,
+}
+
+/// Runs the interactive browser over a tree built by `directory_analyzer`.
+///
+/// Files listed in `duplicates` are flagged in the listing. Deleting and
+/// moving act on the marked entries, or on the selected one when nothing is
+/// marked, and always ask for confirmation first.
+pub fn browse(tree: SizeTree, duplicates: HashSet<PathBuf>) -> Result<(), Box<dyn Error>> {
+    let mut browser = Browser {
+        current: tree.root(),
+        tree,
+        duplicates,
+        entries: Vec::new(),
+        state: TableState::default(),
+        sort: SortKey::Size,
+        marked: BTreeSet::new(),
+        mode: Mode::Browse,
+        status: String::new(),
+    };
+    browser.refresh(None);
+
+    let mut terminal = ratatui::try_init()?;
+    let result = browser.run(&mut terminal);
+    ratatui::try_restore()?;
+    result
+}
+
+impl Browser {
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<(), Box<dyn Error>> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+
+            match std::mem::replace(&mut self.mode, Mode::Browse) {
+                Mode::Browse => {
+                    if !self.handle_browse_key(key.code) {
+                        return Ok(());
+                    }
+                }
+                Mode::ConfirmDelete(targets, _) => {
+                    if matches!(key.code, KeyCode::Char('y') | KeyCode::Char('Y')) {
+                        self.delete(&targets);
+                    } else {
+                        self.status = "Delete cancelled".to_string();
+                    }
+                }
+                Mode::MoveTo(targets, mut input) => match key.code {
+                    KeyCode::Enter => self.move_to(&targets, Path::new(input.trim())),
+                    KeyCode::Esc => self.status = "Move cancelled".to_string(),
+                    KeyCode::Backspace => {
+                        input.pop();
+                        self.mode = Mode::MoveTo(targets, input);
+                    }
+                    KeyCode::Char(c) => {
+                        input.push(c);
+                        self.mode = Mode::MoveTo(targets, input);
+                    }
+                    _ => self.mode = Mode::MoveTo(targets, input),
+                },
+            }
+        }
+    }
+
+    // Returns false when the browser should exit
+    fn handle_browse_key(&mut self, code: KeyCode) -> bool {
+        self.status.clear();
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Up | KeyCode::Char('k') => self.state.select_previous(),
+            KeyCode::Down | KeyCode::Char('j') => self.state.select_next(),
+            KeyCode::PageUp => self.state.scroll_up_by(20),
+            KeyCode::PageDown => self.state.scroll_down_by(20),
+            KeyCode::Home => self.state.select_first(),
+            KeyCode::End => self.state.select_last(),
+            KeyCode::Enter | KeyCode::Right | KeyCode::Char('l') => {
+                if let Some(id) = self.selected() {
+                    if self.tree.node(id).kind() == NodeKind::Dir {
+                        self.current = id;
+                        self.refresh(None);
+                    }
+                }
+            }
+            KeyCode::Left | KeyCode::Backspace | KeyCode::Char('h') => {
+                if let Some(parent) = self.tree.node(self.current).parent() {
+                    let previous = self.current;
+                    self.current = parent;
+                    self.refresh(Some(previous));
+                }
+            }
+            KeyCode::Char('s') => self.set_sort(SortKey::Size),
+            KeyCode::Char('c') => self.set_sort(SortKey::Count),
+            KeyCode::Char('m') => self.set_sort(SortKey::Modified),
+            KeyCode::Char('n') => self.set_sort(SortKey::Name),
+            KeyCode::Char(' ') => {
+                if let Some(id) = self.selected() {
+                    if !self.marked.remove(&id) {
+                        self.marked.insert(id);
+                    }
+                    self.state.select_next();
+                }
+            }
+            KeyCode::Char('d') => {
+                let targets = self.targets();
+                if !targets.is_empty() {
+                    match self.delete_scope(&targets) {
+                        Ok(scope) => self.mode = Mode::ConfirmDelete(targets, scope),
+                        Err(reason) => self.status = format!("Not deleting: {}", reason),
+                    }
+                }
+            }
+            KeyCode::Char('v') => {
+                let targets = self.targets();
+                if !targets.is_empty() {
+                    self.mode = Mode::MoveTo(targets, String::new());
+                }
+            }
+            _ => {}
+        }
+        true
+    }
+
+    fn selected(&self) -> Option<usize> {
+        self.state.selected().and_then(|i| self.entries.get(i).copied())
+    }
+
+    fn targets(&self) -> Vec<usize> {
+        if self.marked.is_empty() {
+            self.selected().into_iter().collect()
+        } else {
+            self.marked.iter().copied().collect()
+        }
+    }
+
+    fn set_sort(&mut self, sort: SortKey) {
+        let selected = self.selected();
+        self.sort = sort;
+        self.refresh(selected);
+    }
+
+    fn refresh(&mut self, select: Option<usize>) {
+        self.entries = self.tree.sorted_children(self.current, self.sort);
+        let index = select
+            .and_then(|id| self.entries.iter().position(|entry| *entry == id))
+            .unwrap_or(0)
+            .min(self.entries.len().saturating_sub(1));
+        self.state
+            .select((!self.entries.is_empty()).then_some(index));
+    }
+
+    fn delete(&mut self, targets: &[usize]) {
+        let mut errors = Vec::new();
+        let mut deleted = 0;
+        for &id in targets {
+            let node = self.tree.node(id);
+            // The directory may have gained entries since the prompt
+            if let Err(reason) = self.delete_scope(&[id]) {
+                errors.push(reason);
+                continue;
+            }
+            let result = match node.kind() {
+                NodeKind::Dir => fs::remove_dir_all(node.path()),
+                NodeKind::File => fs::remove_file(node.path()),
+            };
+            match result {
+                Ok(()) => {
+                    self.tree.detach(id);
+                    self.marked.remove(&id);
+                    deleted += 1;
+                }
+                Err(e) => errors.push(format!("{}: {}", node.name(), e)),
+            }
+        }
+        self.finish_action("Deleted", deleted, errors);
+    }
+
+    fn delete_scope(&self, targets: &[usize]) -> Result<DeleteScope, String> {
+        let mut scope = DeleteScope::default();
+        for &id in targets {
+            self.add_delete_scope(self.tree.node(id).path(), &mut scope)?;
+        }
+        Ok(scope)
+    }
+
+    /// Adds what deleting `path` removes to `scope`. Fails when `path` holds
+    /// a file or directory the scan didn't count (ignored, excluded, past the
+    /// depth or size limits, or on a skipped filesystem), since
+    /// `remove_dir_all` would take that too. Symlinks and special files hold
+    /// no data and never show up in the tree, so they are allowed. Every link
+    /// of a hard-linked file is in the tree; its bytes are counted once.
+    fn add_delete_scope(&self, path: &Path, scope: &mut DeleteScope) -> Result<(), String> {
+        let error = |e: io::Error| format!("{}: {}", path.display(), e);
+        let metadata = fs::symlink_metadata(path).map_err(error)?;
+        let data = metadata.is_dir() || metadata.is_file();
+        if data && self.tree.find(path).is_none() {
+            return Err(format!("{} was not part of the scan", path.display()));
+        }
+        if !metadata.is_dir() {
+            scope.files += 1;
+            let id = utils::hard_link_id(&metadata);
+            if id.is_none_or(|id| scope.linked.insert(id)) {
+                scope.bytes += metadata.len();
+            }
+            return Ok(());
+        }
+        scope.folders += 1;
+        for entry in fs::read_dir(path).map_err(error)? {
+            self.add_delete_scope(&entry.map_err(error)?.path(), scope)?;
+        }
+        Ok(())
+    }
+
+    fn move_to(&mut self, targets: &[usize], destination: &Path) {
+        if destination.as_os_str().is_empty() {
+            self.status = "Move cancelled".to_string();
+            return;
+        }
+        let into_dir = destination.is_dir();
+        if targets.len() > 1 && !into_dir {
+            self.status = format!(
+                "Not moving: {} is not a directory, and {} items can't all go to one path",
+                destination.display(),
+                targets.len()
+            );
+            return;
+        }
+        let mut errors = Vec::new();
+        let mut moved = 0;
+        for &id in targets {
+            let node = self.tree.node(id);
+            let target = if into_dir {
+                destination.join(node.path().file_name().unwrap_or_default())
+            } else {
+                destination.to_path_buf()
+            };
+            // rename() would silently replace an existing file
+            if fs::symlink_metadata(&target).is_ok() {
+                errors.push(format!("{}: {} already exists", node.name(), target.display()));
+                continue;
+            }
+            match move_path(node.path(), &target) {
+                Ok(()) => {
+                    self.tree.detach(id);
+                    self.marked.remove(&id);
+                    // Keep the entry visible if it moved somewhere inside the tree
+                    let target = target.canonicalize().unwrap_or(target);
+                    if let Some(parent) = target.parent().and_then(|p| self.tree.find(p)) {
+                        self.tree.attach(id, parent, target);
+                    }
+                    moved += 1;
+                }
+                Err(e) => errors.push(format!("{}: {}", node.name(), e)),
+            }
+        }
+        self.finish_action("Moved", moved, errors);
+    }
+
+    fn finish_action(&mut self, action: &str, count: usize, errors: Vec<String>) {
+        let selected = self.state.selected();
+        self.entries = self.tree.sorted_children(self.current, self.sort);
+        self.state.select(
+            selected
+                .filter(|_| !self.entries.is_empty())
+                .map(|i| i.min(self.entries.len() - 1)),
+        );
+        self.status = match errors.first() {
+            None => format!("{} {} item(s)", action, count),
+            Some(first) => format!(
+                "{} {} item(s), {} failed ({})",
+                action,
+                count,
+                errors.len(),
+                first
+            ),
+        };
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [header, body, footer] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(1),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let current = self.tree.node(self.current);
+        frame.render_widget(
+            Paragraph::new(format!(
+                "📂 {}  {}  {} files  sort: {:?}  marked: {}",
+                current.path().display(),
+                utils::format_size(current.size()),
+                utils::format_number(current.file_count()),
+                self.sort,
+                self.marked.len()
+            ))
+            .style(Style::new().add_modifier(Modifier::BOLD)),
+            header,
+        );
+
+        let total = (*current.size()).max(1);
+        let rows = self.entries.iter().map(|&id| {
+            let node = self.tree.node(id);
+            let ratio = *node.size() as f64 / total as f64;
+            let filled = (ratio * BAR_WIDTH as f64).round() as usize;
+            let name = match node.kind() {
+                NodeKind::Dir => format!("{}/", node.name()),
+                NodeKind::File => node.name(),
+            };
+            let flags = format!(
+                "{}{}",
+                if self.marked.contains(&id) { "*" } else { " " },
+                if self.duplicates.contains(node.path()) { "=" } else { " " }
+            );
+            let style = if self.marked.contains(&id) {
+                Style::new().fg(Color::Yellow)
+            } else {
+                Style::new()
+            };
+            Row::new(vec![
+                Cell::from(flags),
+                Cell::from(utils::format_size(node.size())),
+                Cell::from(format!("{:>5.1}%", ratio * 100.0)),
+                Cell::from(format!(
+                    "[{}{}]",
+                    "#".repeat(filled.min(BAR_WIDTH)),
+                    " ".repeat(BAR_WIDTH - filled.min(BAR_WIDTH))
+                )),
+                Cell::from(utils::format_number(node.file_count())),
+                Cell::from(
+                    node.modified()
+                        .as_ref()
+                        .map(utils::format_datetime)
+                        .unwrap_or_default(),
+                ),
+                Cell::from(name),
+            ])
+            .style(style)
+        });
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(2),
+                Constraint::Length(10),
+                Constraint::Length(6),
+                Constraint::Length(BAR_WIDTH as u16 + 2),
+                Constraint::Length(10),
+                Constraint::Length(19),
+                Constraint::Min(10),
+            ],
+        )
+        .header(
+            Row::new(vec!["", "Size", "%", "", "Files", "Modified", "Name"])
+                .style(Style::new().add_modifier(Modifier::UNDERLINED)),
+        )
+        .block(Block::default().borders(Borders::TOP | Borders::BOTTOM))
+        .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(table, body, &mut self.state);
+
+        let footer_text = match &self.mode {
+            Mode::Browse if self.status.is_empty() => HELP.to_string(),
+            Mode::Browse => self.status.clone(),
+            Mode::ConfirmDelete(targets, scope) => format!(
+                "Delete {} item(s): {} files, {} folders, {}? [y/N]",
+                targets.len(),
+                utils::format_number(&scope.files),
+                utils::format_number(&scope.folders),
+                utils::format_size(&scope.bytes)
+            ),
+            Mode::MoveTo(targets, input) => {
+                format!("Move {} item(s) to: {}▏ (Esc cancels)", targets.len(), input)
+            }
+        };
+        frame.render_widget(Paragraph::new(Line::from(footer_text)), footer);
+    }
+}
+
+// Falls back to copy + delete for files when a rename crosses filesystems
+fn move_path(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Ok(()) => Ok(()),
+        Err(e) if from.is_file() && e.kind() == io::ErrorKind::CrossesDevices => {
+            fs::copy(from, to)?;
+            fs::remove_file(from)
+        }
+        Err(e) => Err(e),
+    }
+}
@@ -1,29 +1,224 @@
-use std::{fs::File, io::{self, BufRead, BufReader}, path::Path, time::SystemTime};
+use std::{collections::VecDeque, fs::{File, Metadata}, io::{self, BufRead, BufReader}, path::{Path, PathBuf}, time::{Duration, SystemTime}};
 
-use chrono::{DateTime, Local, NaiveDate, NaiveTime, Utc};
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, SecondsFormat, Utc};
+use serde::{de, ser::SerializeSeq, Deserialize, Deserializer, Serializer};
 
-pub fn content_exists_in_file(file_path: &Path, search_string: &str) -> io::Result<Option<(usize, String)>> {
+use crate::model::{ContentMatch, ContentMatchOptions, ContentMatcher, ContextLine, LineMatch};
+
+// Longest line, in bytes, kept around a match or as a context line
+const MAX_LINE_DISPLAY: usize = 50;
+// Bytes inspected at the start of a file to decide whether it is binary
+const BINARY_SNIFF_SIZE: usize = 8 * 1024;
+
+/// Matches a file's content, skipping binary files unless the options ask
+/// for them.
+pub fn content_matches_in_file(
+    file_path: &Path,
+    matcher: &ContentMatcher,
+    options: &ContentMatchOptions,
+) -> io::Result<ContentMatch> {
     let file = File::open(file_path)?;
-    let reader = BufReader::new(file);
-
-    for (line_number, line_result) in reader.lines().enumerate() {
-        let line = line_result?; // Handle potential IO errors
-        if let Some(index) = line.find(search_string) {
-            let max_length = 20;
-            if line.len() <= max_length{
-                
-                return Ok(Some((line_number + 1, line))); 
-            }
-            let truncated_line = truncate_around_substring(&line, search_string, index, 50);
-            return Ok(Some((line_number + 1, truncated_line))); 
+    let mut reader = BufReader::new(file);
+
+    let head = reader.fill_buf()?;
+    if is_binary(&head[..head.len().min(BINARY_SNIFF_SIZE)]) {
+        if !options.binary() {
+            return Ok(ContentMatch::SkippedBinary);
         }
-        if line.contains(search_string) {
+        let offsets = binary_matches(reader, matcher, options)?;
+        return Ok(if offsets.is_empty() {
+            ContentMatch::NoMatch
+        } else {
+            ContentMatch::Binary(offsets)
+        });
+    }
+
+    let matches = line_matches(reader, matcher, options)?;
+    Ok(if matches.is_empty() {
+        ContentMatch::NoMatch
+    } else {
+        ContentMatch::Lines(matches)
+    })
+}
 
-            return Ok(Some((line_number + 1, line))); 
+/// A NUL byte marks a file as binary. Text in other encodings, like
+/// Latin-1, is still searched line by line.
+pub fn is_binary(sample: &[u8]) -> bool {
+    sample.contains(&0)
+}
+
+fn binary_matches(
+    mut reader: impl BufRead,
+    matcher: &ContentMatcher,
+    options: &ContentMatchOptions,
+) -> io::Result<Vec<u64>> {
+    let max_count = options.max_count().unwrap_or(usize::MAX);
+    let mut offsets = Vec::new();
+    let mut offset = 0u64;
+    let mut buffer = Vec::new();
+
+    while offsets.len() < max_count {
+        buffer.clear();
+        let count = reader.read_until(b'\n', &mut buffer)?;
+        if count == 0 {
+            break;
         }
+        offsets.extend(
+            matcher
+                .find_bytes(&buffer)
+                .map(|start| offset + start as u64)
+                .take(max_count - offsets.len()),
+        );
+        offset += count as u64;
     }
 
-    Ok(None) // Pattern not found
+    Ok(offsets)
+}
+
+/// Collects every matching line of a file with its surrounding context.
+fn line_matches(
+    mut reader: impl BufRead,
+    matcher: &ContentMatcher,
+    options: &ContentMatchOptions,
+) -> io::Result<Vec<LineMatch>> {
+    let max_count = options.max_count().unwrap_or(usize::MAX);
+
+    let mut matches: Vec<LineMatch> = Vec::new();
+    let mut before: VecDeque<ContextLine> = VecDeque::with_capacity(options.before_context());
+    let mut after_remaining = 0;
+    let mut buffer = Vec::new();
+    let mut line_number = 0;
+
+    loop {
+        buffer.clear();
+        if reader.read_until(b'\n', &mut buffer)? == 0 {
+            break;
+        }
+        line_number += 1;
+        // Invalid UTF-8, such as Latin-1 text, shouldn't hide a match
+        let line = String::from_utf8_lossy(&buffer);
+        let line = line.trim_end_matches(['\n', '\r']);
+
+        let found = if matches.len() < max_count {
+            matcher.find(line)
+        } else {
+            None
+        };
+        match found {
+            Some(found) => {
+                let (truncated_line, start, end) =
+                    truncate_around_substring(line, found.start, found.end, MAX_LINE_DISPLAY);
+                let mut line_match =
+                    LineMatch::new(line_number, found.start + 1, truncated_line, start, end);
+                line_match.set_context_before(before.drain(..).collect());
+                matches.push(line_match);
+                after_remaining = options.after_context();
+            }
+            None if after_remaining > 0 => {
+                after_remaining -= 1;
+                let (truncated_line, _, _) = truncate_around_substring(line, 0, 0, MAX_LINE_DISPLAY);
+                if let Some(last) = matches.last_mut() {
+                    last.push_context_after(ContextLine::new(line_number, truncated_line));
+                }
+            }
+            None if matches.len() >= max_count => break,
+            None if options.before_context() > 0 => {
+                if before.len() == options.before_context() {
+                    before.pop_front();
+                }
+                let (truncated_line, _, _) = truncate_around_substring(line, 0, 0, MAX_LINE_DISPLAY);
+                before.push_back(ContextLine::new(line_number, truncated_line));
+            }
+            None => {}
+        }
+    }
+
+    Ok(matches)
+}
+
+/// Bytes allocated on disk for a file (`st_blocks * 512`); the apparent
+/// length where block counts aren't available.
+pub fn disk_usage(metadata: &Metadata) -> u64 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        metadata.blocks() * 512
+    }
+    #[cfg(not(unix))]
+    {
+        metadata.len()
+    }
+}
+
+/// `(device, inode)` of a file with more than one hard link.
+pub fn hard_link_id(metadata: &Metadata) -> Option<(u64, u64)> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        (metadata.nlink() > 1).then(|| (metadata.dev(), metadata.ino()))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        None
+    }
+}
+
+/// Device a file lives on, `None` where that isn't exposed.
+pub fn device_id(metadata: &Metadata) -> Option<u64> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        Some(metadata.dev())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        None
+    }
+}
+
+/// `(uid, gid)` owning a file, `None` where ownership isn't exposed.
+pub fn owner_ids(metadata: &Metadata) -> Option<(u32, u32)> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        Some((metadata.uid(), metadata.gid()))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        None
+    }
+}
+
+/// Permission bits (including setuid, setgid and sticky) of a file.
+pub fn permissions(metadata: &Metadata) -> Option<u32> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        Some(metadata.mode() & 0o7777)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        None
+    }
+}
+
+/// Last inode change (`ctime`), or the modification time elsewhere.
+pub fn changed_time(metadata: &Metadata) -> Option<SystemTime> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let seconds = u64::try_from(metadata.ctime()).ok()?;
+        let nanos = u32::try_from(metadata.ctime_nsec()).ok()?;
+        SystemTime::UNIX_EPOCH.checked_add(Duration::new(seconds, nanos))
+    }
+    #[cfg(not(unix))]
+    {
+        metadata.modified().ok()
+    }
 }
 
 pub fn format_size(bytes: &u64) -> String {
@@ -38,16 +233,22 @@ pub fn format_size(bytes: &u64) -> String {
 
     format!("{:.1} {}", size, units[unit_index])
 }
+pub fn format_size_delta(delta: i64) -> String {
+    let sign = if delta < 0 { "-" } else { "+" };
+    format!("{}{}", sign, format_size(&delta.unsigned_abs()))
+}
+
+/// Pads or shortens `path` to `max_length` characters, cutting out the middle.
 pub fn truncate_path(path: &str, max_length: usize) -> String {
-    if path.len() <= max_length {
+    let length = path.chars().count();
+    if length <= max_length {
         format!("{:<width$}", path, width = max_length)
     } else {
-        let half = (max_length - 3) / 2;
-        format!(
-            "{}...{}",
-            &path[..half],
-            &path[path.len() - (max_length - half - 3)..]
-        )
+        let half = max_length.saturating_sub(3) / 2;
+        let tail = max_length.saturating_sub(half + 3);
+        let head: String = path.chars().take(half).collect();
+        let tail: String = path.chars().skip(length - tail).collect();
+        format!("{}...{}", head, tail)
     }
 }
 
@@ -57,7 +258,7 @@ pub fn format_number(num: &u64) -> String {
     let mut formatted: Vec<char> = Vec::with_capacity(len + len / 3);
 
     for (i, c) in num_str.chars().enumerate() {
-        if i > 0 && (len - i) % 3 == 0 {
+        if i > 0 && (len - i).is_multiple_of(3) {
             formatted.push(' ');
         }
         formatted.push(c);
@@ -72,47 +273,71 @@ pub fn format_datetime(time: &SystemTime) -> String {
 }
 
 
-fn truncate_around_substring(s: &str, sub: &str,index: usize, max_length: usize) -> String {
-
+/// Shortens `s` to about `max_length` bytes of context around the match at
+/// `start..end`, returning the new line and the match span within it.
+pub fn truncate_around_substring(
+    s: &str,
+    start: usize,
+    end: usize,
+    max_length: usize,
+) -> (String, usize, usize) {
     if s.len() <= max_length {
-        return s.to_string();
+        return (s.to_string(), start, end);
     }
-   
-   let sub_len = sub.len();
+
+    let sub_len = end - start;
 
     let available_context = max_length.saturating_sub(sub_len);
     let mut left_context = available_context / 2;
     let mut right_context = available_context - left_context;
 
-    if left_context > index {
-        let extra = left_context - index;
-        left_context = index;
+    if left_context > start {
+        let extra = left_context - start;
+        left_context = start;
         right_context += extra;
     }
-    
-    let right_available = s.len() - (index + sub_len);
+
+    let right_available = s.len() - end;
     if right_context > right_available {
         let extra = right_context - right_available;
         right_context = right_available;
         left_context = left_context.saturating_add(extra);
-        if left_context > index {
-            left_context = index;
+        if left_context > start {
+            left_context = start;
         }
     }
 
-    let start = index.saturating_sub(left_context);
-    let end = (index + sub_len).saturating_add(right_context);
+    let mut context_start = start.saturating_sub(left_context);
+    while !s.is_char_boundary(context_start) {
+        context_start -= 1;
+    }
+    let mut context_end = end.saturating_add(right_context);
+    while !s.is_char_boundary(context_end) {
+        context_end += 1;
+    }
 
     let mut result = String::new();
-    if start > 0 {
+    if context_start > 0 {
         result.push_str("...");
     }
-    result.push_str(&s[start..end]);
-    if end < s.len() {
+    let offset = result.len();
+    result.push_str(&s[context_start..context_end]);
+    if context_end < s.len() {
         result.push_str("...");
     }
 
-    result
+    let match_start = start - context_start + offset;
+    (result, match_start, match_start + sub_len)
+}
+
+/// Wraps the `start..end` byte range of `line` in ANSI bold red.
+pub fn highlight_span(line: &str, start: usize, end: usize) -> String {
+    format!(
+        "{}\x1b[1;31m{}\x1b[0m{}",
+        &line[..start],
+        &line[start..end],
+        &line[end..]
+    )
 }
 
 pub fn parse_date(input: Option<String>, field: &str) -> Result<Option<SystemTime>, String> {
@@ -126,7 +351,241 @@ pub fn parse_date(input: Option<String>, field: &str) -> Result<Option<SystemTim
     }).transpose()
 }
 
+const AGE_UNITS: [(&str, u64); 6] = [
+    ("y", 365 * 24 * 60 * 60),
+    ("w", 7 * 24 * 60 * 60),
+    ("d", 24 * 60 * 60),
+    ("h", 60 * 60),
+    ("m", 60),
+    ("s", 1),
+];
+
+/// Parses durations such as `90s`, `30m`, `12h`, `30d`, `2w` or `1y` (365
+/// days), for ages and timeouts alike; callers say which option was wrong.
+pub fn parse_age(value: &str) -> Result<Duration, String> {
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: u64 = number.parse().map_err(|_| {
+        format!("invalid duration '{}', expected a number and a unit, e.g. 30d", value)
+    })?;
+    let Some((_, seconds)) = AGE_UNITS.iter().find(|(name, _)| *name == unit) else {
+        return Err(format!("unknown duration unit '{}' (use s, m, h, d, w or y)", unit));
+    };
+    Ok(Duration::from_secs(number.saturating_mul(*seconds)))
+}
+
+/// Formats an age in the largest unit that divides it evenly, the way
+/// `parse_age` reads it back.
+pub fn format_age(age: &Duration) -> String {
+    let seconds = age.as_secs();
+    let (name, unit) = AGE_UNITS
+        .iter()
+        .find(|(_, unit)| seconds > 0 && seconds.is_multiple_of(*unit))
+        .unwrap_or(&("s", 1));
+    format!("{}{}", seconds / unit, name)
+}
+
 pub fn convert_system_time(time: SystemTime) -> String {
     let datetime: DateTime<Utc> = time.into(); 
     datetime.format("%Y-%m-%d").to_string()
-}
\ No newline at end of file
+}
+
+pub fn format_rfc3339(time: &SystemTime) -> String {
+    let datetime: DateTime<Utc> = (*time).into();
+    datetime.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+// Serde helpers shared by the machine-readable output formats
+
+pub fn serialize_age<S: Serializer>(
+    age: &Option<Duration>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match age {
+        Some(age) => serializer.serialize_some(&format_age(age)),
+        None => serializer.serialize_none(),
+    }
+}
+
+pub fn serialize_path<S: Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&path.to_string_lossy())
+}
+
+pub fn serialize_paths<S: Serializer>(paths: &[PathBuf], serializer: S) -> Result<S::Ok, S::Error> {
+    let mut seq = serializer.serialize_seq(Some(paths.len()))?;
+    for path in paths {
+        seq.serialize_element(&path.to_string_lossy())?;
+    }
+    seq.end()
+}
+
+pub fn serialize_rfc3339<S: Serializer>(time: &SystemTime, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format_rfc3339(time))
+}
+
+// Keeps sub-second precision so snapshot mtimes round-trip exactly
+pub fn serialize_rfc3339_precise<S: Serializer>(
+    time: &SystemTime,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let datetime: DateTime<Utc> = (*time).into();
+    serializer.serialize_str(&datetime.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+}
+
+pub fn deserialize_rfc3339<'de, D: Deserializer<'de>>(deserializer: D) -> Result<SystemTime, D::Error> {
+    let input = String::deserialize(deserializer)?;
+    DateTime::parse_from_rfc3339(&input)
+        .map(SystemTime::from)
+        .map_err(de::Error::custom)
+}
+
+pub fn serialize_duration_ms<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(duration.as_millis() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    // Matches `pattern` in a temp file holding `lines`, one per line
+    fn line_matches_in(
+        lines: &[&str],
+        pattern: &str,
+        options: ContentMatchOptions,
+    ) -> Vec<LineMatch> {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("lines.txt");
+        fs::write(&path, lines.join("\n")).unwrap();
+        let matcher = options.build_matcher(pattern).unwrap();
+        match content_matches_in_file(&path, &matcher, &options).unwrap() {
+            ContentMatch::Lines(matches) => matches,
+            ContentMatch::NoMatch => Vec::new(),
+            _ => panic!("lines.txt was not read as text"),
+        }
+    }
+
+    fn numbers(lines: &[ContextLine]) -> Vec<usize> {
+        lines.iter().map(ContextLine::line_number).collect()
+    }
+
+    const LINES: [&str; 9] = ["a", "b", "hit", "c", "hit", "d", "e", "f", "hit"];
+
+    #[test]
+    fn context_is_not_repeated_between_close_matches() {
+        let options = ContentMatchOptions::default().with_context(2, 2);
+        let matches = line_matches_in(&LINES, "hit", options);
+
+        let found: Vec<usize> = matches.iter().map(LineMatch::line_number).collect();
+        assert_eq!(found, [3, 5, 9]);
+        assert_eq!(numbers(matches[0].context_before()), [1, 2]);
+        // Line 4 sits between two matches and is shown once
+        assert_eq!(numbers(matches[0].context_after()), [4]);
+        assert!(matches[1].context_before().is_empty());
+        assert_eq!(numbers(matches[1].context_after()), [6, 7]);
+        assert_eq!(numbers(matches[2].context_before()), [8]);
+        assert!(matches[2].context_after().is_empty());
+    }
+
+    #[test]
+    fn before_and_after_context_are_independent() {
+        let options = ContentMatchOptions::default().with_context(1, 0);
+        let matches = line_matches_in(&LINES, "hit", options);
+        assert_eq!(numbers(matches[0].context_before()), [2]);
+        assert_eq!(numbers(matches[1].context_before()), [4]);
+        assert!(matches.iter().all(|found| found.context_after().is_empty()));
+
+        let options = ContentMatchOptions::default().with_context(0, 1);
+        let matches = line_matches_in(&LINES, "hit", options);
+        assert!(matches.iter().all(|found| found.context_before().is_empty()));
+        assert_eq!(numbers(matches[0].context_after()), [4]);
+        assert_eq!(numbers(matches[1].context_after()), [6]);
+    }
+
+    #[test]
+    fn max_count_stops_after_the_last_match_context() {
+        let options = ContentMatchOptions::default()
+            .with_max_count(Some(2))
+            .with_context(0, 3);
+        let matches = line_matches_in(&LINES, "hit", options);
+
+        assert_eq!(matches.len(), 2);
+        // The third match, line 9, is neither reported nor shown as context
+        assert_eq!(numbers(matches[1].context_after()), [6, 7, 8]);
+    }
+
+    #[test]
+    fn match_options_shape_the_pattern() {
+        let lines = ["Hit", "hitting", "a.c", "abc"];
+        let found = |pattern: &str, options: ContentMatchOptions| -> Vec<usize> {
+            line_matches_in(&lines, pattern, options)
+                .iter()
+                .map(LineMatch::line_number)
+                .collect()
+        };
+
+        let ignore_case = ContentMatchOptions::default().with_case_insensitive(true);
+        assert_eq!(found("hit", ContentMatchOptions::default()), [2]);
+        assert_eq!(found("hit", ignore_case), [1, 2]);
+        assert_eq!(found("hit", ignore_case.with_whole_word(true)), [1]);
+        assert_eq!(found("a.c", ContentMatchOptions::default()), [3]);
+        assert_eq!(found("a.c", ContentMatchOptions::default().with_regex(true)), [3, 4]);
+    }
+
+    #[test]
+    fn truncation_cuts_between_characters() {
+        let path = "/données/日本語のファイル名/résumé.txt";
+        let truncated = truncate_path(path, 20);
+        assert_eq!(truncated, "/données...ésumé.txt");
+        assert_eq!(truncated.chars().count(), 20);
+        assert_eq!(truncate_path("/é", 4), "/é  ");
+
+        let line = "日本語のテキストの中に needle があります。".repeat(3);
+        let start = line.find("needle").unwrap();
+        let (shown, from, to) = truncate_around_substring(&line, start, start + 6, 12);
+        assert_eq!(&shown[from..to], "needle");
+    }
+
+    #[test]
+    fn only_nul_bytes_mark_a_file_as_binary() {
+        let temp = tempfile::tempdir().unwrap();
+        let latin1 = temp.path().join("latin1.txt");
+        fs::write(&latin1, b"caf\xe9 au lait\nna\xefve\n").unwrap();
+        let binary = temp.path().join("data.bin");
+        fs::write(&binary, b"na\xefve\0\n").unwrap();
+
+        let options = ContentMatchOptions::default();
+        let matcher = options.build_matcher("ve").unwrap();
+        match content_matches_in_file(&latin1, &matcher, &options).unwrap() {
+            ContentMatch::Lines(matches) => assert_eq!(matches[0].line_number(), 2),
+            _ => panic!("latin1.txt was not read as text"),
+        }
+        assert!(matches!(
+            content_matches_in_file(&binary, &matcher, &options).unwrap(),
+            ContentMatch::SkippedBinary
+        ));
+    }
+
+    #[test]
+    fn whole_words_may_start_or_end_with_punctuation() {
+        let lines = ["xfoo(1)", "foo(x)", "call foo( 1 )", "foo_bar foo"];
+        let whole_word = ContentMatchOptions::default().with_whole_word(true);
+
+        let matches = line_matches_in(&lines, "foo(", whole_word);
+        assert_eq!(matches.len(), 1);
+        assert_eq!((matches[0].line_number(), matches[0].column()), (3, 6));
+
+        // The first candidate is joined to `_bar`, the second stands alone
+        let matches = line_matches_in(&lines, "foo", whole_word);
+        let found: Vec<(usize, usize)> =
+            matches.iter().map(|found| (found.line_number(), found.column())).collect();
+        assert_eq!(found, [(2, 1), (3, 6), (4, 9)]);
+
+        let matcher = whole_word.with_binary(true).build_matcher("foo").unwrap();
+        let offsets: Vec<usize> = matcher.find_bytes(b"foo\0foox foo").collect();
+        assert_eq!(offsets, [0, 9]);
+    }
+}
@@ -0,0 +1,240 @@
+use std::fs::{self, Metadata};
+use std::path::Path;
+use std::sync::mpsc::{self, SyncSender};
+use std::thread;
+
+use ignore::{DirEntry, WalkBuilder, WalkState};
+
+use crate::utils;
+
+// Number of in-flight items between the walker threads and the aggregator
+const CHANNEL_CAPACITY: usize = 4096;
+
+/// Sends items from a walker thread to the aggregating thread.
+pub struct Emitter<T> {
+    sender: SyncSender<T>,
+}
+
+impl<T> Emitter<T> {
+    pub fn emit(&self, item: T) {
+        // The receiver only goes away once aggregation is done
+        let _ = self.sender.send(item);
+    }
+}
+
+// Per-directory ignore file read on top of `.gitignore` and `.ignore`
+const TOOL_IGNORE_FILENAME: &str = ".fsignore";
+const GIT_DIR: &str = ".git";
+
+/// Sets up a walk over everything below `root`. With `respect_ignore_files`,
+/// nested `.gitignore`, `.ignore` and `.fsignore` files, `.git/info/exclude`
+/// and the global git excludes are honored (negations included), whether or
+/// not `root` is inside a git repository, and `.git` itself is skipped.
+pub fn walk_builder(
+    root: &Path,
+    max_depth: Option<usize>,
+    follow_links: bool,
+    respect_ignore_files: bool,
+    threads: usize,
+) -> WalkBuilder {
+    let mut builder = WalkBuilder::new(root);
+    builder
+        .standard_filters(false)
+        .follow_links(follow_links)
+        .max_depth(max_depth)
+        .threads(threads);
+    if respect_ignore_files {
+        builder
+            .git_ignore(true)
+            .git_global(true)
+            .git_exclude(true)
+            .ignore(true)
+            .parents(true)
+            .require_git(false)
+            .add_custom_ignore_filename(TOOL_IGNORE_FILENAME)
+            // Repository internals are never ignored by the files above
+            .filter_entry(|entry| entry.file_name() != GIT_DIR);
+    }
+    builder
+}
+
+/// Whether `entry` is a directory on another device than its parent, i.e.
+/// something is mounted there. Only directories are checked, which costs an
+/// extra `stat` of the parent each.
+pub fn is_mount_point(entry: &DirEntry, metadata: &Metadata) -> bool {
+    if entry.depth() == 0 || !metadata.is_dir() {
+        return false;
+    }
+    let Some(device) = utils::device_id(metadata) else {
+        return false;
+    };
+    entry
+        .path()
+        .parent()
+        .and_then(|parent| fs::metadata(parent).ok())
+        .and_then(|parent| utils::device_id(&parent))
+        .is_some_and(|parent_device| parent_device != device)
+}
+
+/// Walks the tree with a work-stealing pool of threads.
+///
+/// `visit` runs on the walker threads and forwards whatever it wants to keep
+/// through the `Emitter`; `aggregate` runs on the calling thread and sees
+/// every emitted item, so it can use plain mutable state.
+pub fn walk_parallel<T, V, A>(builder: &WalkBuilder, visit: V, mut aggregate: A)
+where
+    T: Send,
+    V: Fn(DirEntry, &Emitter<T>) -> WalkState + Sync,
+    A: FnMut(T),
+{
+    let (sender, receiver) = mpsc::sync_channel(CHANNEL_CAPACITY);
+    let walker = builder.build_parallel();
+
+    thread::scope(|scope| {
+        let visit = &visit;
+        scope.spawn(move || {
+            walker.run(|| {
+                let emitter = Emitter {
+                    sender: sender.clone(),
+                };
+                Box::new(move |result| match result {
+                    Ok(entry) => visit(entry, &emitter),
+                    Err(e) => {
+                        eprintln!("Warning: {}", e);
+                        WalkState::Continue
+                    }
+                })
+            });
+            // Dropping the last sender ends the aggregation loop below
+            drop(sender);
+        });
+
+        for item in receiver {
+            aggregate(item);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::model::{AnalyzeOptions, HashAlgorithm, SearchOptions};
+    use crate::{directory_analyzer, file_finder};
+
+    // Paths below `root` that a walk emits, sorted
+    fn walk(root: &Path, respect_ignore_files: bool) -> Vec<PathBuf> {
+        let builder = walk_builder(root, None, false, respect_ignore_files, 2);
+        let mut paths = Vec::new();
+        walk_parallel(
+            &builder,
+            |entry, emitter| {
+                emitter.emit(entry.path().strip_prefix(root).unwrap().to_path_buf());
+                WalkState::Continue
+            },
+            |path| paths.push(path),
+        );
+        paths.retain(|path| !path.as_os_str().is_empty());
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn ignore_files_are_honored_and_git_is_skipped() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        for dir in [".git/hooks", "build", "src/generated", "notes"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        let files = [
+            (".gitignore", "build/\n*.log\n!keep.log\n"),
+            (".git/HEAD", "ref: refs/heads/main\n"),
+            (".git/hooks/pre-commit.sample", ""),
+            ("build/out.o", ""),
+            ("debug.log", ""),
+            ("keep.log", ""),
+            ("src/main.rs", ""),
+            ("src/.fsignore", "generated\n"),
+            ("src/generated/table.rs", ""),
+            ("notes/todo.txt", ""),
+        ];
+        for (path, content) in files {
+            fs::write(root.join(path), content).unwrap();
+        }
+
+        let expected: Vec<PathBuf> = [
+            ".gitignore",
+            "keep.log",
+            "notes",
+            "notes/todo.txt",
+            "src",
+            "src/.fsignore",
+            "src/main.rs",
+        ]
+        .iter()
+        .map(PathBuf::from)
+        .collect();
+        assert_eq!(walk(root, true), expected);
+        // Without ignore handling everything is walked, `.git` included
+        assert_eq!(walk(root, false).len(), files.len() + 6);
+    }
+
+    // Summary, snapshot and inventory order as JSON, without the timings
+    fn analyze_json(root: &Path, threads: usize) -> serde_json::Value {
+        let options = AnalyzeOptions::builder(root.to_path_buf())
+            .with_top_n(25)
+            .with_threads(threads)
+            .with_duplicates(true)
+            .with_hash_algorithm(HashAlgorithm::Xxh3)
+            .with_inventory(true)
+            .with_snapshot(true)
+            .with_by_type(true)
+            .build()
+            .unwrap();
+        let summary = directory_analyzer(&options).unwrap();
+        let inventory: Vec<_> = summary.inventory().iter().flatten().map(|e| e.path()).collect();
+        let mut json = serde_json::json!({
+            "summary": summary,
+            "snapshot": summary.snapshot(),
+            "inventory": inventory,
+        });
+        json["summary"]["duration_ms"].take();
+        json["snapshot"]["created"].take();
+        json
+    }
+
+    fn search_json(root: &Path, threads: usize) -> serde_json::Value {
+        let options = SearchOptions::builder(root.to_path_buf())
+            .with_content_pattern(Some("line 1".to_string()))
+            .with_threads(threads)
+            .build()
+            .unwrap();
+        let mut json = serde_json::to_value(file_finder(&options).unwrap()).unwrap();
+        json["search_time_ms"].take();
+        json
+    }
+
+    #[test]
+    fn results_do_not_depend_on_thread_count() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        // Many equal sizes and contents, so ties have to be broken the same way
+        for dir in 0..6 {
+            let dir = root.join(format!("dir{}", dir)).join("nested");
+            fs::create_dir_all(&dir).unwrap();
+            for file in 0..40 {
+                let content = format!("line {}\n", file % 9).repeat(file % 5 + 1);
+                fs::write(dir.join(format!("file{}.txt", file)), content).unwrap();
+            }
+        }
+
+        let analyzed = analyze_json(root, 1);
+        let searched = search_json(root, 1);
+        for _ in 0..3 {
+            assert_eq!(analyze_json(root, 8), analyzed);
+            assert_eq!(search_json(root, 8), searched);
+        }
+    }
+}